@@ -0,0 +1,50 @@
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lox_scanner::lox_language_scanner::Scanner;
+use lox_scanner::parallel::scan_parallel;
+
+const ITERATIONS: u32 = 10;
+
+fn main() {
+    let program = r#"
+        // variables and math
+        var x = 42;
+        var y = 3.14;
+        print "hello, world";
+        if (x >= y) {
+            x = x + 1;
+        } else {
+            y = y - 1;
+        }
+    "#;
+    let src = program.repeat(32 * 1024 * 1024 / program.len());
+    let threads = thread::available_parallelism().map_or(4, |n| n.get());
+
+    println!(
+        "Input size: {} MiB, threads: {}",
+        src.len() / (1024 * 1024),
+        threads
+    );
+
+    let sequential = measure(|| Scanner::new(&src).count());
+    println!("Scanner:       {:?}", sequential);
+
+    let parallel = measure(|| scan_parallel(&src, threads).len());
+    println!("scan_parallel: {:?}", parallel);
+
+    println!(
+        "Speedup: {:.2}x",
+        sequential.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
+
+fn measure(f: impl Fn() -> usize) -> Duration {
+    black_box(f());
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f());
+    }
+    start.elapsed() / ITERATIONS
+}
@@ -1,13 +1,24 @@
 use std::env;
+use std::fmt::Write;
+use std::fs;
 use std::path::Path;
 use std::process::Command;
 
+include!("src/token_spec.rs");
+
 fn main() {
     let scanner_src_path = Path::new("src").join("lox_language_scanner.re2c.rs");
     println!("cargo::rerun-if-changed={}", scanner_src_path.display());
+    println!("cargo::rerun-if-changed=src/token_spec.rs");
 
     println!("Run build.rs");
     let out_dir = env::var_os("OUT_DIR").unwrap();
+
+    // Generated from the token specification: Rust code included by the scanner and re2c rules included by re2c
+    let tokens_out_path = Path::new(&out_dir).join("tokens.rs");
+    fs::write(&tokens_out_path, generate_token_types()).expect("failed to write tokens.rs");
+    fs::write(Path::new(&out_dir).join("tokens.re"), generate_re2c_rules())
+        .expect("failed to write tokens.re");
     let re2c_exe_path = Path::new(&out_dir).join("re2c.exe");
 
     if !re2c_exe_path.exists() {
@@ -24,6 +35,9 @@ fn main() {
         .arg(&scanner_src_path)
         .arg("-o")
         .arg(&scanner_out_path)
+        .arg("-I")
+        .arg(&out_dir)
+        .arg("--conditions")
         .arg("-W")
         .arg("-Werror")
         .arg("--lang")
@@ -37,6 +51,7 @@ fn main() {
 
     status = Command::new("rustfmt")
         .arg(&scanner_out_path)
+        .arg(&tokens_out_path)
         .status()
         .expect("failed to run rustfmt");
 
@@ -47,7 +62,137 @@ fn main() {
 
 fn powershell_execute_script(script: &str) {
     std::process::Command::new("powershell")
-        .args(["-NoProfile", "-Command", &script])
+        .args(["-NoProfile", "-Command", script])
         .status()
         .unwrap();
 }
+
+// Fixed tokens of the enabled cargo features, as (name, lexeme).
+fn fixed_tokens() -> Vec<(&'static str, &'static str)> {
+    FIXED_TOKENS
+        .iter()
+        .filter(|(_, _, feature)| {
+            let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+            feature.is_empty() || env::var_os(var).is_some()
+        })
+        .map(|&(name, lexeme, _)| (name, lexeme))
+        .collect()
+}
+
+fn generate_token_types() -> String {
+    let fixed_tokens = fixed_tokens();
+    let fixed = || fixed_tokens.iter().map(|(name, _)| *name);
+    let all = || fixed().chain(VALUE_TOKENS.iter().map(|(name, _)| *name));
+    let mut out = String::new();
+
+    out.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+    out.push_str("#[allow(non_camel_case_types, dead_code)]\n");
+    out.push_str("pub enum TokenType<'a> {\n");
+    for name in fixed() {
+        writeln!(out, "    {name},").unwrap();
+    }
+    for (name, payload) in VALUE_TOKENS {
+        match *payload {
+            "" => writeln!(out, "    {name},").unwrap(),
+            payload => writeln!(out, "    {name}({payload}),").unwrap(),
+        }
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("/// Payload-free discriminant of a `TokenType`, convenient for storing and comparing token kinds.\n");
+    out.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n");
+    out.push_str("#[allow(non_camel_case_types)]\n");
+    out.push_str("pub enum TokenKind {\n");
+    for name in all() {
+        writeln!(out, "    {name},").unwrap();
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("impl TokenKind {\n");
+    out.push_str("    pub const ALL: &'static [TokenKind] = &[\n");
+    for name in all() {
+        writeln!(out, "        TokenKind::{name},").unwrap();
+    }
+    out.push_str("    ];\n\n");
+    out.push_str("    // Source text of the tokens that always look the same. Tokens with a value don't have a fixed text.\n");
+    out.push_str("    pub fn fixed_lexeme(self) -> Option<&'static str> {\n");
+    out.push_str("        match self {\n");
+    for (name, lexeme) in &fixed_tokens {
+        writeln!(out, "            TokenKind::{name} => Some({lexeme:?}),").unwrap();
+    }
+    for (name, _) in VALUE_TOKENS {
+        writeln!(out, "            TokenKind::{name} => None,").unwrap();
+    }
+    out.push_str("        }\n    }\n\n");
+    out.push_str("    // Token type of the tokens that always look the same.\n");
+    out.push_str("    pub fn fixed_token_type(self) -> Option<TokenType<'static>> {\n");
+    out.push_str("        match self {\n");
+    for name in fixed() {
+        writeln!(
+            out,
+            "            TokenKind::{name} => Some(TokenType::{name}),"
+        )
+        .unwrap();
+    }
+    for (name, _) in VALUE_TOKENS {
+        writeln!(out, "            TokenKind::{name} => None,").unwrap();
+    }
+    out.push_str("        }\n    }\n\n");
+    out.push_str("    pub fn name(self) -> &'static str {\n");
+    out.push_str("        match self {\n");
+    for name in all() {
+        writeln!(out, "            TokenKind::{name} => {name:?},").unwrap();
+    }
+    out.push_str("        }\n    }\n}\n\n");
+
+    out.push_str("impl TokenType<'_> {\n");
+    out.push_str("    pub fn kind(&self) -> TokenKind {\n");
+    out.push_str("        match self {\n");
+    for name in fixed() {
+        writeln!(out, "            TokenType::{name} => TokenKind::{name},").unwrap();
+    }
+    for (name, payload) in VALUE_TOKENS {
+        let pattern = if payload.is_empty() { "" } else { "(..)" };
+        writeln!(
+            out,
+            "            TokenType::{name}{pattern} => TokenKind::{name},"
+        )
+        .unwrap();
+    }
+    out.push_str("        }\n    }\n}\n\n");
+
+    out.push_str("// Prints the name of the kind, for example `LEFT_PAREN`.\n");
+    out.push_str("impl core::fmt::Display for TokenKind {\n");
+    out.push_str("    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {\n");
+    out.push_str("        f.write_str(self.name())\n");
+    out.push_str("    }\n}\n\n");
+
+    out.push_str("// Parses the name of the kind, the inverse of `Display`.\n");
+    out.push_str("impl core::str::FromStr for TokenKind {\n");
+    out.push_str("    type Err = ParseTokenKindError;\n\n");
+    out.push_str("    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {\n");
+    out.push_str("        match s {\n");
+    for name in all() {
+        writeln!(out, "            {name:?} => Ok(TokenKind::{name}),").unwrap();
+    }
+    out.push_str("            _ => Err(ParseTokenKindError),\n");
+    out.push_str("        }\n    }\n}\n");
+    out
+}
+
+fn generate_re2c_rules() -> String {
+    let mut out = String::new();
+    for (name, lexeme) in fixed_tokens() {
+        if HAND_WRITTEN_RULES.contains(&name) {
+            continue;
+        }
+        let lexeme = lexeme.replace('\\', "\\\\").replace('"', "\\\"");
+        // Punctuators and keywords belong to code, the `INIT` condition of the scanner
+        writeln!(
+            out,
+            "<INIT> @beg \"{lexeme}\" @end {{ return self.create_token(TokenType::{name}, beg, end) }}"
+        )
+        .unwrap();
+    }
+    out
+}
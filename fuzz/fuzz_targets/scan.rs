@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lox_scanner::lox_language_scanner::Scanner;
+use lox_scanner_fuzz::check_invariants;
+
+// Arbitrary bytes. Invalid UTF-8 is replaced, so the replacement character gets exercised too.
+fuzz_target!(|data: &[u8]| {
+    let src = String::from_utf8_lossy(data);
+    let items: Vec<_> = Scanner::new(&src).collect();
+    check_invariants(&src, &items);
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lox_scanner::lox_language_scanner::{NewlinePolicy, Scanner};
+use lox_scanner_fuzz::check_invariants;
+
+// The first byte selects the scanner options, the rest is the input.
+fuzz_target!(|data: &[u8]| {
+    let Some((&options, data)) = data.split_first() else {
+        return;
+    };
+    let policy = match options & 0b11 {
+        0 => NewlinePolicy::Any,
+        1 => NewlinePolicy::Unix,
+        2 => NewlinePolicy::Windows,
+        _ => NewlinePolicy::EditorCompatible,
+    };
+
+    let src = String::from_utf8_lossy(data);
+    let items: Vec<_> = Scanner::new(&src)
+        .newline_policy(policy)
+        .report_mixed_line_endings(options & 0b100 != 0)
+        .skip_shebang(options & 0b1000 != 0)
+        .trivia(options & 0b1_0000 != 0)
+        .collect();
+    check_invariants(&src, &items);
+});
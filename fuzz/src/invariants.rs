@@ -0,0 +1,84 @@
+// Invariants that every output of the scanner has to satisfy, whatever the input.
+// Shared by the fuzz targets and by the `invariants` test of the scanner crate, which runs them on the seed corpus.
+
+use anyhow::Result;
+use lox_scanner::lox_language_scanner::{
+    ConfusableCharacterError, MixedLineEndingsError, Token, UnexpectedSymbolError,
+    UnterminatedStringError,
+};
+
+// Position of a token or an error: (line number, start, end).
+fn position(item: &Result<Token>) -> (usize, usize, usize) {
+    match item {
+        Ok(token) => (
+            token.line_number,
+            token.start_index_within_input,
+            token.end_index_within_input,
+        ),
+        Err(err) => {
+            if let Some(err) = err.downcast_ref::<UnexpectedSymbolError>() {
+                (
+                    err.line_number,
+                    err.error_index_within_input,
+                    err.error_index_within_input,
+                )
+            } else if let Some(err) = err.downcast_ref::<ConfusableCharacterError>() {
+                (
+                    err.line_number,
+                    err.error_index_within_input,
+                    err.error_end_index_within_input,
+                )
+            } else if let Some(err) = err.downcast_ref::<MixedLineEndingsError>() {
+                (
+                    err.line_number,
+                    err.error_index_within_input,
+                    err.error_index_within_input,
+                )
+            } else if let Some(err) = err.downcast_ref::<UnterminatedStringError>() {
+                (
+                    err.line_number,
+                    err.error_index_within_input,
+                    err.error_index_within_input,
+                )
+            } else {
+                panic!("unknown scanner error: {err}")
+            }
+        }
+    }
+}
+
+// Panics if the scanner output for `src` breaks an invariant:
+// - `start <= end <= src.len()` and both are on a char boundary,
+// - tokens and errors don't overlap and come in the order of their positions,
+// - line numbers never decrease,
+// - the position within the line agrees with the position within the input.
+pub fn check_invariants(src: &str, items: &[Result<Token>]) {
+    let mut previous_end = 0;
+    let mut previous_line = 0;
+    for item in items {
+        let (line, start, end) = position(item);
+        assert!(start <= end, "{start} > {end} in {item:?}");
+        assert!(
+            end <= src.len(),
+            "{end} is past the end of the input in {item:?}"
+        );
+        assert!(
+            src.is_char_boundary(start) && src.is_char_boundary(end),
+            "{start}..{end} isn't on a char boundary in {item:?}"
+        );
+        assert!(start >= previous_end, "{item:?} overlaps the previous item");
+        assert!(line >= previous_line, "line number decreased in {item:?}");
+
+        if let Ok(token) = item {
+            assert!(token.line_start_index <= start);
+            assert_eq!(
+                token.start_index_within_line,
+                start - token.line_start_index
+            );
+            assert_eq!(token.end_index_within_line, end - token.line_start_index);
+        }
+
+        previous_end = end;
+        previous_line = line;
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::Write;
+
+use crate::keyword_typos::KeywordTypo;
+use crate::lox_language_scanner::{
+    ConfusableCharacterError, MixedLineEndingsError, UnexpectedSymbolError, UnterminatedStringError,
+};
+use crate::visual_column::{DEFAULT_TAB_WIDTH, expand_tabs, visual_column};
+
+// Position of a scanning error within the scanned input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorLocation {
+    pub line_number: usize,
+    pub line_start_index: usize,
+    pub index_within_input: usize,
+}
+
+pub fn error_location(err: &anyhow::Error) -> Option<ErrorLocation> {
+    if let Some(err) = err.downcast_ref::<UnexpectedSymbolError>() {
+        return Some(ErrorLocation {
+            line_number: err.line_number,
+            line_start_index: err.line_start_index_within_input,
+            index_within_input: err.error_index_within_input,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<ConfusableCharacterError>() {
+        return Some(ErrorLocation {
+            line_number: err.line_number,
+            line_start_index: err.line_start_index_within_input,
+            index_within_input: err.error_index_within_input,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<KeywordTypo>() {
+        return Some(ErrorLocation {
+            line_number: err.line_number,
+            line_start_index: err.line_start_index_within_input,
+            index_within_input: err.error_index_within_input,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<MixedLineEndingsError>() {
+        return Some(ErrorLocation {
+            line_number: err.line_number,
+            line_start_index: err.line_start_index_within_input,
+            index_within_input: err.error_index_within_input,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<UnterminatedStringError>() {
+        return Some(ErrorLocation {
+            line_number: err.line_number,
+            line_start_index: err.line_start_index_within_input,
+            index_within_input: err.error_index_within_input,
+        });
+    }
+    None
+}
+
+// Formats a scanning error the way compilers do, with 1-based line and column numbers
+// and the offending line underneath:
+//
+// docs/guide.md:13:7: error: Unexpected symbol `@`
+// print @x;
+//       ^
+pub fn render(path: &str, src: &str, err: &anyhow::Error) -> String {
+    render_with_tab_width(path, src, err, DEFAULT_TAB_WIDTH)
+}
+
+// Columns are visual columns, and tabs in the printed line are expanded, so the caret lines up
+// under the offending symbol regardless of tabs and wide characters.
+pub fn render_with_tab_width(
+    path: &str,
+    src: &str,
+    err: &anyhow::Error,
+    tab_width: usize,
+) -> String {
+    let Some(location) = error_location(err) else {
+        return format!("{path}: error: {err}\n");
+    };
+
+    let line = &src[location.line_start_index..];
+    let line = &line[..line.find(['\r', '\n']).unwrap_or(line.len())];
+    let column = visual_column(
+        line,
+        location.index_within_input - location.line_start_index,
+        tab_width,
+    );
+
+    let mut out = format!(
+        "{path}:{}:{}: error: {}\n",
+        location.line_number + 1,
+        column + 1,
+        message(src, err, &location)
+    );
+    writeln!(out, "{}", expand_tabs(line, tab_width)).unwrap();
+    writeln!(out, "{:column$}^", "").unwrap();
+    out
+}
+
+fn message(src: &str, err: &anyhow::Error, location: &ErrorLocation) -> String {
+    if err.is::<UnexpectedSymbolError>() {
+        // The error might point into the middle of an invalid UTF-8 sequence, or past the end of the input.
+        return match src
+            .get(location.index_within_input..)
+            .and_then(|rest| rest.chars().next())
+        {
+            Some(symbol) => format!("Unexpected symbol `{}`", symbol.escape_debug()),
+            None => "Unexpected symbol".to_string(),
+        };
+    }
+    err.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyword_typos::find_keyword_typos;
+    use crate::lox_language_scanner::Scanner;
+
+    #[test]
+    fn renders_error_with_caret() {
+        let src = "var x = 1;\n  print @x;\n";
+        let err = Scanner::new(src).find_map(Result::err).unwrap();
+        assert_eq!(
+            render("test.lox", src, &err),
+            "test.lox:2:9: error: Unexpected symbol `@`\n  print @x;\n        ^\n"
+        );
+    }
+
+    #[test]
+    fn caret_lines_up_with_tabs_and_wide_characters() {
+        let src = "\tprint \"日本\" @x;\n";
+        let err = Scanner::new(src).find_map(Result::err).unwrap();
+        assert_eq!(
+            render_with_tab_width("test.lox", src, &err, 4),
+            "test.lox:1:18: error: Unexpected symbol `@`\n    print \"日本\" @x;\n                 ^\n"
+        );
+    }
+
+    #[test]
+    fn renders_confusable_character() {
+        let src = "var x = 1\u{FF1B}\n";
+        let err = Scanner::new(src).find_map(Result::err).unwrap();
+        assert_eq!(
+            render("test.lox", src, &err),
+            "test.lox:1:10: error: found U+FF1B FULLWIDTH SEMICOLON; did you mean `;`?\nvar x = 1\u{FF1B}\n         ^\n"
+        );
+    }
+
+    #[test]
+    fn renders_keyword_typo() {
+        let src = "whlie (x) {}\n";
+        let tokens: Vec<_> = Scanner::new(src).map(|r| r.unwrap()).collect();
+        let typo = find_keyword_typos(&tokens).remove(0);
+        assert_eq!(
+            render("test.lox", src, &typo.into()),
+            "test.lox:1:1: error: unknown identifier `whlie`; did you mean `while`?\nwhlie (x) {}\n^\n"
+        );
+    }
+
+    #[test]
+    fn renders_errors_without_location() {
+        let err = anyhow::anyhow!("Something went wrong");
+        assert_eq!(
+            render("test.lox", "", &err),
+            "test.lox: error: Something went wrong\n"
+        );
+    }
+}
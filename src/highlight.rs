@@ -0,0 +1,283 @@
+use std::ops::Range;
+
+use crate::lox_language_scanner::{
+    ConfusableCharacterError, MixedLineEndingsError, Scanner, TokenKind, UnexpectedSymbolError,
+    UnterminatedStringError,
+};
+
+// What a piece of source text is, for syntax highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightClass {
+    Keyword,
+    // Strings, numbers, `true`, `false` and `nil`.
+    Literal,
+    Operator,
+    // Parentheses, braces, ",", "." and ";".
+    Punctuation,
+    Identifier,
+    // Comments and the shebang line.
+    Comment,
+    // Bytes the scanner reported an error for.
+    Error,
+}
+
+impl HighlightClass {
+    // `None` for whitespace and line breaks, and for `CUSTOM` tokens, which only the scanner extension knows.
+    pub fn of(kind: TokenKind) -> Option<HighlightClass> {
+        match kind {
+            TokenKind::TRUE | TokenKind::FALSE | TokenKind::NIL => Some(HighlightClass::Literal),
+            TokenKind::LEFT_PAREN
+            | TokenKind::RIGHT_PAREN
+            | TokenKind::LEFT_BRACE
+            | TokenKind::RIGHT_BRACE
+            | TokenKind::COMMA
+            | TokenKind::DOT
+            | TokenKind::SEMICOLON => Some(HighlightClass::Punctuation),
+            TokenKind::STRING
+            | TokenKind::STRING_START
+            | TokenKind::STRING_PART
+            | TokenKind::STRING_END
+            | TokenKind::RAW_STRING
+            | TokenKind::NUMBER => Some(HighlightClass::Literal),
+            TokenKind::IDENTIFIER => Some(HighlightClass::Identifier),
+            TokenKind::COMMENT | TokenKind::SHEBANG => Some(HighlightClass::Comment),
+            TokenKind::WHITESPACE | TokenKind::NEWLINE | TokenKind::CUSTOM => None,
+            // The remaining tokens are the fixed ones: keywords are words, operators are symbols.
+            kind => match kind.fixed_lexeme() {
+                Some(lexeme) if lexeme.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    Some(HighlightClass::Keyword)
+                }
+                _ => Some(HighlightClass::Operator),
+            },
+        }
+    }
+
+    // CSS class used by `to_html`, for example `lox-keyword`.
+    pub fn css_class(self) -> &'static str {
+        match self {
+            HighlightClass::Keyword => "lox-keyword",
+            HighlightClass::Literal => "lox-literal",
+            HighlightClass::Operator => "lox-operator",
+            HighlightClass::Punctuation => "lox-punctuation",
+            HighlightClass::Identifier => "lox-identifier",
+            HighlightClass::Comment => "lox-comment",
+            HighlightClass::Error => "lox-error",
+        }
+    }
+
+    // SGR parameters used by `to_ansi`. `None` leaves the text in the default color.
+    fn ansi_style(self) -> Option<&'static str> {
+        match self {
+            HighlightClass::Keyword => Some("1;35"),
+            HighlightClass::Literal => Some("32"),
+            HighlightClass::Operator => Some("36"),
+            HighlightClass::Punctuation | HighlightClass::Identifier => None,
+            HighlightClass::Comment => Some("90"),
+            HighlightClass::Error => Some("4;31"),
+        }
+    }
+}
+
+// Splits `src` into consecutive ranges that cover all of it, each with its class.
+// Uses the scanner in trivia mode, so the result always agrees with how the code is scanned.
+// The range of every scanning error is classified as `HighlightClass::Error`, also where it overlaps a token,
+// like the quotation marks of a string with typographic quotes, or the line break that cuts off an interpolated
+// string. A byte order mark stays unclassified.
+pub fn highlight(src: &str) -> Vec<(Range<usize>, Option<HighlightClass>)> {
+    // Tokens and errors, and for every byte the index of the last of them that covers it. Errors come later,
+    // so they win over the tokens they overlap.
+    let mut pieces = Vec::new();
+    let mut owners: Vec<Option<usize>> = vec![None; src.len()];
+    let mut errors = Vec::new();
+    let scanner = Scanner::new(src)
+        .trivia(true)
+        .skip_shebang(true)
+        .report_mixed_line_endings(true);
+    for item in scanner {
+        match item {
+            Ok(token) => pieces.push((
+                token.lexeme_range(src),
+                HighlightClass::of(token.token_type.kind()),
+            )),
+            Err(err) => errors.extend(error_range(src, &err)),
+        }
+    }
+    pieces.extend(
+        errors
+            .into_iter()
+            .map(|range| (range, Some(HighlightClass::Error))),
+    );
+    for (i, (range, _)) in pieces.iter().enumerate() {
+        owners[range.clone()].fill(Some(i));
+    }
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for end in 1..=src.len() {
+        if end == src.len() || owners[end] != owners[start] {
+            let class = owners[start].and_then(|i| pieces[i].1);
+            ranges.push((start..end, class));
+            start = end;
+        }
+    }
+    ranges
+}
+
+// Bytes a scanning error points at: the unexpected character, or the line ending for errors reported there.
+// `None` for errors at the end of the input.
+fn error_range(src: &str, err: &anyhow::Error) -> Option<Range<usize>> {
+    if let Some(err) = err.downcast_ref::<ConfusableCharacterError>() {
+        return Some(err.error_index_within_input..err.error_end_index_within_input);
+    }
+    let start = if let Some(err) = err.downcast_ref::<MixedLineEndingsError>() {
+        let span = err.span();
+        return Some(span.start..span.end);
+    } else if let Some(err) = err.downcast_ref::<UnexpectedSymbolError>() {
+        err.error_index_within_input
+    } else if let Some(err) = err.downcast_ref::<UnterminatedStringError>() {
+        err.error_index_within_input
+    } else {
+        return None;
+    };
+    let len = src[start..].chars().next()?.len_utf8();
+    Some(start..start + len)
+}
+
+// Source with ANSI escape sequences, for terminals.
+pub fn to_ansi(src: &str) -> String {
+    let mut out = String::with_capacity(src.len() * 2);
+    for (range, class) in highlight(src) {
+        match class.and_then(HighlightClass::ansi_style) {
+            Some(style) => {
+                out.push_str(&format!("\x1b[{style}m"));
+                out.push_str(&src[range]);
+                out.push_str("\x1b[0m");
+            }
+            None => out.push_str(&src[range]),
+        }
+    }
+    out
+}
+
+// Source as HTML, with a `<span>` with the CSS class of `HighlightClass::css_class` around every classified token.
+// Put it into a `<pre>` element. The page provides the styles.
+pub fn to_html(src: &str) -> String {
+    let mut out = String::with_capacity(src.len() * 2);
+    for (range, class) in highlight(src) {
+        match class {
+            Some(class) => {
+                out.push_str(&format!("<span class=\"{}\">", class.css_class()));
+                escape_html(&mut out, &src[range]);
+                out.push_str("</span>");
+            }
+            None => escape_html(&mut out, &src[range]),
+        }
+    }
+    out
+}
+
+fn escape_html(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes(src: &str) -> Vec<(&str, Option<HighlightClass>)> {
+        highlight(src)
+            .into_iter()
+            .map(|(range, class)| (&src[range], class))
+            .collect()
+    }
+
+    #[test]
+    fn classifies_every_byte() {
+        use HighlightClass::*;
+        assert_eq!(
+            classes("var x = nil; // note\nprint(x@);"),
+            [
+                ("var", Some(Keyword)),
+                (" ", None),
+                ("x", Some(Identifier)),
+                (" ", None),
+                ("=", Some(Operator)),
+                (" ", None),
+                ("nil", Some(Literal)),
+                (";", Some(Punctuation)),
+                (" ", None),
+                ("// note", Some(Comment)),
+                ("\n", None),
+                ("print", Some(Keyword)),
+                ("(", Some(Punctuation)),
+                ("x", Some(Identifier)),
+                ("@", Some(Error)),
+                (")", Some(Punctuation)),
+                (";", Some(Punctuation)),
+            ]
+        );
+    }
+
+    #[test]
+    fn strings_include_their_delimiters() {
+        use HighlightClass::*;
+        assert_eq!(
+            classes("\"a${b}c\" \"open"),
+            [
+                ("\"a${", Some(Literal)),
+                ("b", Some(Identifier)),
+                ("}c\"", Some(Literal)),
+                (" ", None),
+                ("\"", Some(Error)),
+                ("open", Some(Identifier)),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_without_a_lexeme_are_highlighted() {
+        use HighlightClass::*;
+        assert_eq!(
+            classes("\"a${b\r\nc\n"),
+            [
+                ("\"a${", Some(Literal)),
+                ("b", Some(Identifier)),
+                // Cuts off the interpolated string
+                ("\r", Some(Error)),
+                ("\n", None),
+                ("c", Some(Identifier)),
+                // Differs from the first line ending
+                ("\n", Some(Error)),
+            ]
+        );
+        assert_eq!(
+            classes("\u{feff}x \u{201C}s\u{201D}"),
+            [
+                ("\u{feff}", None),
+                ("x", Some(Identifier)),
+                (" ", None),
+                ("\u{201C}", Some(Error)),
+                ("s", Some(Literal)),
+                ("\u{201D}", Some(Error)),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_html_and_ansi() {
+        assert_eq!(
+            to_html("a<b @"),
+            "<span class=\"lox-identifier\">a</span><span class=\"lox-operator\">&lt;</span>\
+             <span class=\"lox-identifier\">b</span> <span class=\"lox-error\">@</span>"
+        );
+        assert_eq!(to_ansi("if x"), "\x1b[1;35mif\x1b[0m x");
+    }
+}
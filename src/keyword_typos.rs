@@ -0,0 +1,242 @@
+use thiserror::Error;
+
+use crate::lox_language_scanner::{FileId, Token, TokenKind, TokenType};
+
+// Keywords that start a statement, so they are likely at the beginning of a line.
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "class", "else", "for", "fun", "if", "print", "return", "var", "while",
+];
+
+// Keywords that can be directly followed by "(".
+const CALL_LIKE_KEYWORDS: &[&str] = &["for", "if", "print", "return", "while"];
+
+// Identifier in a place where a keyword is expected, that looks like a misspelled keyword.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("unknown identifier `{found}`; did you mean `{suggestion}`?")]
+pub struct KeywordTypo {
+    pub file_id: FileId,
+    pub line_number: usize,
+    pub line_start_index_within_input: usize,
+    pub error_index_within_line: usize,
+    pub error_index_within_input: usize,
+    pub error_end_index_within_input: usize,
+    pub found: String,
+    pub suggestion: &'static str,
+}
+
+impl KeywordTypo {
+    // Machine-applicable fix: the byte range to replace and the replacement text.
+    pub fn replacement(&self) -> (std::ops::Range<usize>, &'static str) {
+        (
+            self.error_index_within_input..self.error_end_index_within_input,
+            self.suggestion,
+        )
+    }
+}
+
+// Finds identifiers that are probably misspelled keywords, like `whlie (x)` or `retrun x;`.
+// Only identifiers at the beginning of a line or right before "(" are considered,
+// and only if the program doesn't declare them, so calls like `foo(x)` of a declared `fun foo` are fine.
+// Properties, like `obj.paint()`, are never keywords.
+pub fn find_keyword_typos(tokens: &[Token]) -> Vec<KeywordTypo> {
+    let declared = declared_names(tokens);
+
+    let mut typos = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let TokenType::IDENTIFIER(name) = token.token_type else {
+            continue;
+        };
+        let after_dot = i > 0 && tokens[i - 1].token_type.kind() == TokenKind::DOT;
+        if after_dot || declared.contains(&name) {
+            continue;
+        }
+
+        let at_line_start = i == 0 || tokens[i - 1].line_number != token.line_number;
+        let before_paren = tokens
+            .get(i + 1)
+            .is_some_and(|next| next.token_type.kind() == TokenKind::LEFT_PAREN);
+        let candidates = match (at_line_start, before_paren) {
+            (true, _) => STATEMENT_KEYWORDS,
+            (false, true) => CALL_LIKE_KEYWORDS,
+            (false, false) => continue,
+        };
+
+        if let Some(keyword) = closest_keyword(name, candidates) {
+            typos.push(KeywordTypo {
+                file_id: token.file_id,
+                line_number: token.line_number,
+                line_start_index_within_input: token.line_start_index,
+                error_index_within_line: token.start_index_within_line,
+                error_index_within_input: token.start_index_within_input,
+                error_end_index_within_input: token.end_index_within_input,
+                found: name.to_string(),
+                suggestion: keyword,
+            });
+        }
+    }
+    typos
+}
+
+// Names of variables, functions, classes, parameters and methods.
+fn declared_names<'a>(tokens: &[Token<'a>]) -> Vec<&'a str> {
+    let mut declared = Vec::new();
+    let mut depth: usize = 0;
+    // Brace depths of the class bodies we are in. Methods are declared directly inside of them.
+    let mut class_bodies = Vec::new();
+    let mut in_class_header = false;
+    let mut parameters_follow = false;
+    let mut in_parameters = false;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let kind = |i: usize| tokens.get(i).map(|token| token.token_type.kind());
+        match token.token_type {
+            TokenType::IDENTIFIER(name) => {
+                let after_keyword = i > 0
+                    && matches!(
+                        kind(i - 1),
+                        Some(TokenKind::VAR | TokenKind::FUN | TokenKind::CLASS)
+                    );
+                let is_method = class_bodies.last() == Some(&depth)
+                    && kind(i + 1) == Some(TokenKind::LEFT_PAREN);
+                if after_keyword || is_method || in_parameters {
+                    declared.push(name);
+                }
+                parameters_follow |= is_method || i > 0 && kind(i - 1) == Some(TokenKind::FUN);
+            }
+            TokenType::LEFT_PAREN => {
+                in_parameters = parameters_follow;
+                parameters_follow = false;
+            }
+            TokenType::RIGHT_PAREN => in_parameters = false,
+            TokenType::CLASS => in_class_header = true,
+            TokenType::LEFT_BRACE => {
+                depth += 1;
+                if in_class_header {
+                    class_bodies.push(depth);
+                    in_class_header = false;
+                }
+            }
+            TokenType::RIGHT_BRACE => {
+                if class_bodies.last() == Some(&depth) {
+                    class_bodies.pop();
+                }
+                depth = depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+    declared
+}
+
+// Short keywords allow a single edit, otherwise almost every short identifier would be a typo.
+fn max_distance(keyword: &str) -> usize {
+    if keyword.len() <= 4 { 1 } else { 2 }
+}
+
+fn closest_keyword(name: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    // One and two letter names are too short to tell a typo from a deliberate name.
+    if name.len() < 3 {
+        return None;
+    }
+    candidates
+        .iter()
+        .map(|&keyword| (keyword, edit_distance(name, keyword)))
+        .filter(|&(keyword, distance)| distance <= max_distance(keyword))
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(keyword, _)| keyword)
+}
+
+// Damerau-Levenshtein distance (optimal string alignment): insertions, deletions, substitutions
+// and transpositions of two adjacent characters all cost one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox_language_scanner::Scanner;
+
+    fn typos(src: &str) -> Vec<(String, &'static str)> {
+        let tokens: Vec<_> = Scanner::new(src).map(|r| r.unwrap()).collect();
+        find_keyword_typos(&tokens)
+            .into_iter()
+            .map(|typo| (typo.found, typo.suggestion))
+            .collect()
+    }
+
+    #[test]
+    fn edit_distance_counts_transpositions_once() {
+        assert_eq!(edit_distance("whlie", "while"), 1);
+        assert_eq!(edit_distance("retrun", "return"), 1);
+        assert_eq!(edit_distance("pritn", "print"), 1);
+        assert_eq!(edit_distance("fn", "fun"), 1);
+        assert_eq!(edit_distance("clas", "class"), 1);
+        assert_eq!(edit_distance("abc", ""), 3);
+    }
+
+    #[test]
+    fn flags_identifiers_in_keyword_position() {
+        let src = "whlie (x) {\n  retrun x;\n}\nvar y = wile;\nprnt(y);\nfi (y) pritn y;";
+        // `wile` and `pritn` aren't in keyword position, `fi` is too short to tell.
+        assert_eq!(
+            typos(src),
+            [
+                ("whlie".to_string(), "while"),
+                ("retrun".to_string(), "return"),
+                ("prnt".to_string(), "print"),
+            ]
+        );
+    }
+
+    #[test]
+    fn declared_names_are_not_typos() {
+        assert!(typos("fun foo(a) {}\nfoo(1);\nfoo(2);").is_empty());
+
+        // Parameters and methods are declared too
+        let src = "fun draw(prnt) {\n  prnt(1);\n}\nclass Canvas < Base {\n  paint(retrn) {\n    retrn(1);\n  }\n}\npaint(2);";
+        assert!(typos(src).is_empty());
+    }
+
+    #[test]
+    fn properties_are_not_typos() {
+        assert!(typos("canvas.paint();\ncanvas\n  .paint();\ncanvas.\npaint;").is_empty());
+        // Without the dot it could be `print`
+        assert_eq!(typos("paint(1);"), [("paint".to_string(), "print")]);
+    }
+
+    #[test]
+    fn typo_points_at_identifier() {
+        let src = "x = 1;\n  retrun x;";
+        let tokens: Vec<_> = Scanner::new(src).map(|r| r.unwrap()).collect();
+        let typo = &find_keyword_typos(&tokens)[0];
+        assert_eq!(typo.line_number, 1);
+        assert_eq!(typo.error_index_within_line, 2);
+        assert_eq!(typo.replacement(), (9..15, "return"));
+        assert_eq!(
+            typo.to_string(),
+            "unknown identifier `retrun`; did you mean `return`?"
+        );
+    }
+}
@@ -0,0 +1,32 @@
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+pub mod lox_language_scanner {
+    include!(concat!(env!("OUT_DIR"), "/lox_language_scanner.rs"));
+}
+
+#[cfg(feature = "std")]
+pub mod diagnostics;
+#[cfg(feature = "std")]
+pub mod highlight;
+#[cfg(feature = "std")]
+pub mod keyword_typos;
+#[cfg(feature = "std")]
+pub mod markdown;
+#[cfg(feature = "std")]
+pub mod minify;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(feature = "std")]
+pub mod source_map;
+#[cfg(feature = "std")]
+pub mod token_buffer;
+#[cfg(feature = "std")]
+pub mod token_cursor;
+#[cfg(feature = "std")]
+pub mod token_diff;
+#[cfg(feature = "std")]
+pub mod token_printer;
+#[cfg(feature = "std")]
+pub mod token_tree;
+#[cfg(feature = "std")]
+pub mod visual_column;
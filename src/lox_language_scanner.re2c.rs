@@ -1,60 +1,39 @@
+#[cfg(feature = "std")]
 use anyhow::Result;
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq)]
-#[allow(non_camel_case_types, dead_code)]
-pub enum TokenType<'a> {
-    // Single-character tokens.
-    LEFT_PAREN,
-    RIGHT_PAREN,
-    LEFT_BRACE,
-    RIGHT_BRACE,
-    COMMA,
-    DOT,
-    MINUS,
-    PLUS,
-    SEMICOLON,
-    SLASH,
-    STAR,
-
-    // One or two character tokens.
-    BANG,
-    BANG_EQUAL,
-    EQUAL,
-    EQUAL_EQUAL,
-    GREATER,
-    GREATER_EQUAL,
-    LESS,
-    LESS_EQUAL,
-
-    // Literals.
-    IDENTIFIER(&'a str),
-    STRING(&'a str),
-    NUMBER(f64),
-
-    // Keywords.
-    AND,
-    CLASS,
-    ELSE,
-    FALSE,
-    FUN,
-    FOR,
-    IF,
-    NIL,
-    OR,
-    PRINT,
-    RETURN,
-    SUPER,
-    THIS,
-    TRUE,
-    VAR,
-    WHILE,
-}
-
-#[derive(Debug)]
+// Without the `std` feature the scanner only depends on `core`.
+// Errors aren't boxed into `anyhow::Error` then, they are returned as a plain `ScanError` value.
+#[cfg(not(feature = "std"))]
+pub type Result<T> = core::result::Result<T, ScanError>;
+
+// `TokenType`, `TokenKind` and their impls are generated by build.rs from `token_spec.rs`.
+include!(concat!(env!("OUT_DIR"), "/tokens.rs"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Unknown token kind")]
+pub struct ParseTokenKindError;
+
+impl TokenKind {
+    // Whitespace, line breaks, comments and the shebang line. They don't affect the meaning of a program.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            TokenKind::WHITESPACE | TokenKind::NEWLINE | TokenKind::COMMENT | TokenKind::SHEBANG
+        )
+    }
+}
+
+// Identifies a source file within a `SourceMap`. Input scanned without a source map belongs to `FileId(0)`,
+// which a `SourceMap` never hands out: its files are numbered from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct FileId(pub u32);
+
+#[derive(Debug, PartialEq)]
 #[allow(dead_code)]
 pub struct Token<'a> {
     pub token_type: TokenType<'a>,
+    pub file_id: FileId,
     pub line_number: usize,
     pub line_start_index: usize,
     pub start_index_within_input: usize,
@@ -63,36 +42,534 @@ pub struct Token<'a> {
     pub end_index_within_line: usize,
 }
 
-#[derive(Debug, Error)]
+impl<'a> Token<'a> {
+    // Full source text of the token. `src` must be the input the token was scanned from.
+    // Unlike the token position, the text of a string includes the quotation marks,
+    // and the text of the parts of an interpolated string includes the "${" and "}" around the interpolations.
+    pub fn lexeme<'s>(&self, src: &'s str) -> &'s str {
+        &src[self.lexeme_range(src)]
+    }
+
+    // Byte range of `lexeme` within `src`.
+    pub fn lexeme_range(&self, src: &str) -> core::ops::Range<usize> {
+        let (mut beg, mut end) = (self.start_index_within_input, self.end_index_within_input);
+        match self.token_type {
+            TokenType::STRING(_) => {
+                beg -= src[..beg].chars().next_back().map_or(0, char::len_utf8);
+                end += src[end..].chars().next().map_or(0, char::len_utf8);
+            }
+            TokenType::STRING_START(_) | TokenType::STRING_PART(_) => {
+                beg -= 1;
+                end += 2;
+            }
+            TokenType::STRING_END(_) => {
+                beg -= 1;
+                end += 1;
+            }
+            TokenType::RAW_STRING(_) => {
+                let hashes = src[..beg - 1]
+                    .bytes()
+                    .rev()
+                    .take_while(|&c| c == b'#')
+                    .count();
+                beg -= hashes + 2;
+                end += hashes + 1;
+            }
+            _ => {}
+        }
+        beg..end
+    }
+
+    // Text of the literal itself: the name of an identifier, the content of a string without the quotation marks
+    // or the digits of a number. Returns `None` for other tokens.
+    pub fn literal_content<'s>(&self, src: &'s str) -> Option<&'s str> {
+        match self.token_type {
+            TokenType::IDENTIFIER(_)
+            | TokenType::STRING(_)
+            | TokenType::STRING_START(_)
+            | TokenType::STRING_PART(_)
+            | TokenType::STRING_END(_)
+            | TokenType::RAW_STRING(_)
+            | TokenType::NUMBER(_) => {
+                Some(&src[self.start_index_within_input..self.end_index_within_input])
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
 #[error("Unexpected symbol at {line_number}:{error_index_within_line}")]
 pub struct UnexpectedSymbolError {
+    pub file_id: FileId,
+    pub line_number: usize,
+    pub line_start_index_within_input: usize,
+    pub error_index_within_line: usize,
+    pub error_index_within_input: usize,
+}
+
+// Character that looks like a Lox symbol but isn't one, usually pasted from a word processor or a chat tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Confusable {
+    pub symbol: char,
+    // Unicode name of the symbol.
+    pub name: &'static str,
+    // What the user most likely meant.
+    pub suggestion: &'static str,
+}
+
+#[rustfmt::skip]
+pub const CONFUSABLES: &[Confusable] = &[
+    Confusable { symbol: '\u{201C}', name: "LEFT DOUBLE QUOTATION MARK", suggestion: "\"" },
+    Confusable { symbol: '\u{201D}', name: "RIGHT DOUBLE QUOTATION MARK", suggestion: "\"" },
+    Confusable { symbol: '\u{201E}', name: "DOUBLE LOW-9 QUOTATION MARK", suggestion: "\"" },
+    Confusable { symbol: '\u{00AB}', name: "LEFT-POINTING DOUBLE ANGLE QUOTATION MARK", suggestion: "\"" },
+    Confusable { symbol: '\u{00BB}', name: "RIGHT-POINTING DOUBLE ANGLE QUOTATION MARK", suggestion: "\"" },
+    Confusable { symbol: '\u{2013}', name: "EN DASH", suggestion: "-" },
+    Confusable { symbol: '\u{2014}', name: "EM DASH", suggestion: "-" },
+    Confusable { symbol: '\u{2212}', name: "MINUS SIGN", suggestion: "-" },
+    Confusable { symbol: '\u{00A0}', name: "NO-BREAK SPACE", suggestion: " " },
+    Confusable { symbol: '\u{2007}', name: "FIGURE SPACE", suggestion: " " },
+    Confusable { symbol: '\u{202F}', name: "NARROW NO-BREAK SPACE", suggestion: " " },
+    Confusable { symbol: '\u{3000}', name: "IDEOGRAPHIC SPACE", suggestion: " " },
+    Confusable { symbol: '\u{FF1B}', name: "FULLWIDTH SEMICOLON", suggestion: ";" },
+    Confusable { symbol: '\u{037E}', name: "GREEK QUESTION MARK", suggestion: ";" },
+    Confusable { symbol: '\u{FF0C}', name: "FULLWIDTH COMMA", suggestion: "," },
+    Confusable { symbol: '\u{FF0E}', name: "FULLWIDTH FULL STOP", suggestion: "." },
+    Confusable { symbol: '\u{FF08}', name: "FULLWIDTH LEFT PARENTHESIS", suggestion: "(" },
+    Confusable { symbol: '\u{FF09}', name: "FULLWIDTH RIGHT PARENTHESIS", suggestion: ")" },
+    Confusable { symbol: '\u{FF5B}', name: "FULLWIDTH LEFT CURLY BRACKET", suggestion: "{" },
+    Confusable { symbol: '\u{FF5D}', name: "FULLWIDTH RIGHT CURLY BRACKET", suggestion: "}" },
+    Confusable { symbol: '\u{FF1D}', name: "FULLWIDTH EQUALS SIGN", suggestion: "=" },
+    Confusable { symbol: '\u{FF0B}', name: "FULLWIDTH PLUS SIGN", suggestion: "+" },
+    Confusable { symbol: '\u{00D7}', name: "MULTIPLICATION SIGN", suggestion: "*" },
+    Confusable { symbol: '\u{2215}', name: "DIVISION SLASH", suggestion: "/" },
+    Confusable { symbol: '\u{2260}', name: "NOT EQUAL TO", suggestion: "!=" },
+    Confusable { symbol: '\u{2264}', name: "LESS-THAN OR EQUAL TO", suggestion: "<=" },
+    Confusable { symbol: '\u{2265}', name: "GREATER-THAN OR EQUAL TO", suggestion: ">=" },
+];
+
+impl Confusable {
+    pub fn find(symbol: char) -> Option<&'static Confusable> {
+        CONFUSABLES
+            .iter()
+            .find(|confusable| confusable.symbol == symbol)
+    }
+}
+
+// Replacing the bytes from `error_index_within_input` to `error_end_index_within_input` with `suggestion`
+// fixes the error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("found U+{:04X} {name}; did you mean `{suggestion}`?", u32::from(*.symbol))]
+pub struct ConfusableCharacterError {
+    pub file_id: FileId,
+    pub line_number: usize,
+    pub line_start_index_within_input: usize,
+    pub error_index_within_line: usize,
+    pub error_index_within_input: usize,
+    pub error_end_index_within_input: usize,
+    pub symbol: char,
+    pub name: &'static str,
+    pub suggestion: &'static str,
+}
+
+impl ConfusableCharacterError {
+    // Machine-applicable fix: the byte range to replace and the replacement text.
+    pub fn replacement(&self) -> (core::ops::Range<usize>, &'static str) {
+        (
+            self.error_index_within_input..self.error_end_index_within_input,
+            self.suggestion,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ScanError {
+    #[error(transparent)]
+    UnexpectedSymbol(#[from] UnexpectedSymbolError),
+    #[error(transparent)]
+    MixedLineEndings(#[from] MixedLineEndingsError),
+    #[error(transparent)]
+    ConfusableCharacter(#[from] ConfusableCharacterError),
+    #[error(transparent)]
+    UnterminatedString(#[from] UnterminatedStringError),
+}
+
+// Which character sequences start a new line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NewlinePolicy {
+    // Only "\n" ("\r\n" too, because it ends with "\n"). A lone "\r" is whitespace.
+    Unix,
+    // Only "\r\n". Lone "\n" and "\r" are whitespace.
+    Windows,
+    // "\r\n", "\n\r", "\r" and "\n" are single line breaks.
+    #[default]
+    Any,
+    // Same as most editors: "\r\n", "\r" and "\n". "\n\r" is two line breaks.
+    EditorCompatible,
+}
+
+impl NewlinePolicy {
+    // Length of the line break at the beginning of `s`, or 0 if `s` doesn't start with one.
+    // Agrees with the line numbers of the scanner, so that other tools can count lines the same way.
+    pub fn line_break_len(self, s: &[u8]) -> usize {
+        match (self, s) {
+            (NewlinePolicy::Any, [b'\n', b'\r', ..]) | (_, [b'\r', b'\n', ..]) => 2,
+            (NewlinePolicy::Windows, _) | (NewlinePolicy::Unix, [b'\r', ..]) => 0,
+            (_, [b'\r' | b'\n', ..]) => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+    LfCr,
+}
+
+impl core::fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::Cr => "CR",
+            LineEnding::LfCr => "LFCR",
+        })
+    }
+}
+
+// Reported once per input, at the first line ending that differs from the first line ending of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Mixed line endings: expected {expected}, found {found}")]
+pub struct MixedLineEndingsError {
+    pub file_id: FileId,
+    pub line_number: usize,
+    pub line_start_index_within_input: usize,
+    pub error_index_within_line: usize,
+    pub error_index_within_input: usize,
+    pub expected: LineEnding,
+    pub found: LineEnding,
+}
+
+// Interpolated string that isn't closed on its line. Reported where the string is cut off: at the line ending,
+// at the end of the input, or right after an interpolation if the rest of the line doesn't continue the string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Unterminated string at {line_number}:{error_index_within_line}")]
+pub struct UnterminatedStringError {
+    pub file_id: FileId,
     pub line_number: usize,
     pub line_start_index_within_input: usize,
     pub error_index_within_line: usize,
     pub error_index_within_input: usize,
+    // Opening quotation mark of the string. For nested interpolated strings, of the outermost one.
+    pub string_start_index_within_input: usize,
+}
+
+// How many interpolations can be nested, like in "a${ "b${ c }" }". A deeper "${" is an unexpected symbol.
+pub const MAX_INTERPOLATION_DEPTH: usize = 16;
+
+// "${ ... }" inside of an interpolated string.
+#[derive(Debug, Clone, Copy, Default)]
+struct Interpolation {
+    // Opening quotation mark of the string.
+    string_start: usize,
+    // Braces opened inside of the interpolation and not closed yet. The interpolation ends at a "}" when it is 0.
+    open_braces: usize,
+}
+
+// re2c conditions: `YYC_INIT` for code, `YYC_STR` for the rest of an interpolated string after an interpolation.
+/*!conditions:re2c*/
+
+// Where the scanned text is located within a larger host document,
+// for example a Lox code block inside of a Markdown file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SourceOrigin {
+    pub file_id: FileId,
+    // Offset of the first byte of the scanned text within the host document.
+    pub byte_offset: usize,
+    // Line of the host document on which the scanned text starts.
+    pub line_number: usize,
+    // Offset of the beginning of that line within the host document.
+    pub line_start_index: usize,
+}
+
+// Lets a host add its own lexemes, like `@annotations` or `#directives`, without changing the Lox grammar.
+pub trait ScannerExtension {
+    // Called when the scanner hits a character that doesn't start any Lox token. `rest` is the input from that
+    // character to the end. Returns the kind of the custom token and its length in bytes, which must be at least 1
+    // and end on a character boundary. Custom tokens can't contain line breaks, since the scanner wouldn't count
+    // the lines. Returning `None` reports the character as unexpected, as usual.
+    fn scan(&mut self, rest: &str) -> Option<(u16, usize)>;
+
+    // `false` if `scan` never returns anything, so the scanner doesn't call it at all.
+    const SCANS: bool = true;
 }
 
-pub struct Scanner<'a> {
+// Plain Lox without custom lexemes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoExtension;
+
+impl ScannerExtension for NoExtension {
+    fn scan(&mut self, _rest: &str) -> Option<(u16, usize)> {
+        None
+    }
+
+    const SCANS: bool = false;
+}
+
+pub struct Scanner<'a, E: ScannerExtension = NoExtension> {
+    // The input, as a string for the extension and as bytes for the generated code
+    src: &'a str,
     s: &'a [u8],
     cursor: usize,
     #[allow(dead_code)]
     mark: usize,
     #[allow(dead_code)]
     ctxmarker: usize,
+    file_id: FileId,
+    // Offset of `s` within the host document. All reported positions are shifted by it.
+    base_index: usize,
     current_line_number: usize,
     current_line_start_index: usize,
+    newline_policy: NewlinePolicy,
+    report_mixed_line_endings: bool,
+    // Line ending style of the input, the first line ending seen.
+    first_line_ending: Option<LineEnding>,
+    mixed_line_endings_reported: bool,
+    skip_shebang: bool,
+    trivia: bool,
+    at_start: bool,
+    // Returned by the next calls to `next`, before scanning anything else. First in, first out.
+    pending: [Option<Result<Token<'a>>>; 3],
+    condition: usize,
+    // Interpolations the scanner is inside of, innermost last.
+    interpolations: [Interpolation; MAX_INTERPOLATION_DEPTH],
+    interpolation_depth: usize,
+    // Opening quotation mark of the interpolated string continued in `YYC_STR`.
+    string_start: usize,
+    extension: E,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(input: &'a str) -> Self {
+        Self::with_origin(input, SourceOrigin::default())
+    }
+
+    // Scans a fragment of a host document. Tokens and errors report positions within the host document.
+    // A UTF-8 byte order mark at the beginning of the host document is skipped. The first line then starts after it,
+    // so columns on the first line are the same as without the byte order mark.
+    pub fn with_origin(input: &'a str, origin: SourceOrigin) -> Self {
+        debug_assert!(origin.line_start_index <= origin.byte_offset);
+        let bom_len = if origin.byte_offset == 0 && input.starts_with('\u{feff}') {
+            '\u{feff}'.len_utf8()
+        } else {
+            0
+        };
         Self {
+            src: input,
             s: input.as_bytes(),
-            cursor: 0,
+            cursor: bom_len,
             mark: 0,
             ctxmarker: 0,
-            current_line_number: 0,
-            current_line_start_index: 0,
+            file_id: origin.file_id,
+            base_index: origin.byte_offset,
+            current_line_number: origin.line_number,
+            current_line_start_index: origin.line_start_index + bom_len,
+            newline_policy: NewlinePolicy::Any,
+            report_mixed_line_endings: false,
+            first_line_ending: None,
+            mixed_line_endings_reported: false,
+            skip_shebang: false,
+            trivia: false,
+            at_start: true,
+            pending: [None, None, None],
+            condition: YYC_INIT,
+            interpolations: [Interpolation::default(); MAX_INTERPOLATION_DEPTH],
+            interpolation_depth: 0,
+            string_start: 0,
+            extension: NoExtension,
+        }
+    }
+}
+
+impl<'a, E: ScannerExtension> Scanner<'a, E> {
+    // Hands characters that don't start a Lox token to `extension` before reporting them as unexpected.
+    pub fn extension<X: ScannerExtension>(self, extension: X) -> Scanner<'a, X> {
+        Scanner {
+            src: self.src,
+            s: self.s,
+            cursor: self.cursor,
+            mark: self.mark,
+            ctxmarker: self.ctxmarker,
+            file_id: self.file_id,
+            base_index: self.base_index,
+            current_line_number: self.current_line_number,
+            current_line_start_index: self.current_line_start_index,
+            newline_policy: self.newline_policy,
+            report_mixed_line_endings: self.report_mixed_line_endings,
+            first_line_ending: self.first_line_ending,
+            mixed_line_endings_reported: self.mixed_line_endings_reported,
+            skip_shebang: self.skip_shebang,
+            trivia: self.trivia,
+            at_start: self.at_start,
+            pending: self.pending,
+            condition: self.condition,
+            interpolations: self.interpolations,
+            interpolation_depth: self.interpolation_depth,
+            string_start: self.string_start,
+            extension,
+        }
+    }
+
+    pub fn newline_policy(mut self, policy: NewlinePolicy) -> Self {
+        self.newline_policy = policy;
+        self
+    }
+
+    // When enabled, the scanner returns a `MixedLineEndingsError` at the first line ending
+    // that differs from the first line ending of the input.
+    pub fn report_mixed_line_endings(mut self, report: bool) -> Self {
+        self.report_mixed_line_endings = report;
+        self
+    }
+
+    // When enabled, a first line starting with "#!", like "#!/usr/bin/env lox", is skipped.
+    pub fn skip_shebang(mut self, skip: bool) -> Self {
+        self.skip_shebang = skip;
+        self
+    }
+
+    // When enabled, the scanner also returns whitespace, line breaks, comments and the shebang line as tokens,
+    // so the tokens cover the whole input.
+    pub fn trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    // Line the scanner is currently on.
+    pub fn current_line_number(&self) -> usize {
+        self.current_line_number
+    }
+
+    fn start_new_line(&mut self) {
+        self.current_line_number += 1;
+        self.current_line_start_index = self.base_index + self.cursor;
+    }
+
+    // Called for "\r\n", "\n\r", "\r" and "\n" between `beg` and `end`.
+    // Starts a new line if the newline policy says so. Returns the unterminated string and the mixed line endings
+    // errors, if any, and the trivia token in trivia mode.
+    fn handle_line_ending(&mut self, beg: usize, end: usize) -> Option<Result<Token<'a>>> {
+        // Strings can't span lines, and neither can the interpolations inside of them.
+        let unterminated = if self.interpolation_depth > 0 {
+            self.create_unterminated_string_error(beg)
+        } else {
+            None
+        };
+
+        let mut ending = match &self.s[beg..end] {
+            b"\r\n" => LineEnding::CrLf,
+            b"\n\r" => LineEnding::LfCr,
+            b"\r" => LineEnding::Cr,
+            _ => LineEnding::Lf,
+        };
+
+        // "\n\r" is a single line break only with `NewlinePolicy::Any`.
+        // Otherwise, it is "\n" followed by "\r", which is scanned again.
+        if ending == LineEnding::LfCr && self.newline_policy != NewlinePolicy::Any {
+            self.cursor = beg + 1;
+            ending = LineEnding::Lf;
+        }
+        let end = self.cursor;
+
+        let error = match self.first_line_ending {
+            None => {
+                self.first_line_ending = Some(ending);
+                None
+            }
+            Some(expected)
+                if expected != ending
+                    && self.report_mixed_line_endings
+                    && !self.mixed_line_endings_reported =>
+            {
+                self.mixed_line_endings_reported = true;
+                let error_index_within_input = self.base_index + beg;
+                Some(Err(MixedLineEndingsError {
+                    file_id: self.file_id,
+                    line_number: self.current_line_number,
+                    line_start_index_within_input: self.current_line_start_index,
+                    error_index_within_line: error_index_within_input
+                        - self.current_line_start_index,
+                    error_index_within_input,
+                    expected,
+                    found: ending,
+                }
+                .into()))
+            }
+            Some(_) => None,
+        };
+
+        let starts_new_line = match self.newline_policy {
+            NewlinePolicy::Any | NewlinePolicy::EditorCompatible => true,
+            NewlinePolicy::Unix => ending != LineEnding::Cr,
+            NewlinePolicy::Windows => ending == LineEnding::CrLf,
+        };
+        // The line break belongs to the line it ends. A line ending that doesn't start a new line is whitespace.
+        let trivia = if !self.trivia {
+            None
+        } else if starts_new_line {
+            self.create_token(TokenType::NEWLINE, beg, end)
+        } else {
+            self.create_token(TokenType::WHITESPACE, beg, end)
+        };
+
+        if starts_new_line {
+            self.start_new_line();
         }
+
+        self.return_in_order([unterminated, error, trivia])
+    }
+
+    // Returns the first of `items` and leaves the rest for the next calls to `next`.
+    fn return_in_order<const N: usize>(
+        &mut self,
+        items: [Option<Result<Token<'a>>>; N],
+    ) -> Option<Result<Token<'a>>> {
+        let mut items = items.into_iter().flatten();
+        let first = items.next();
+        for item in items {
+            self.push_pending(item);
+        }
+        first
+    }
+
+    fn push_pending(&mut self, item: Result<Token<'a>>) {
+        let slot = self.pending.iter_mut().find(|slot| slot.is_none());
+        *slot.expect("too many pending tokens") = Some(item);
+    }
+
+    // The shebang can only be on the first line, so this is called once, before anything else is scanned.
+    fn scan_shebang(&mut self) -> Option<Result<Token<'a>>> {
+        let beg = self.cursor;
+        let rest = &self.s[beg..];
+        if !self.skip_shebang || !rest.starts_with(b"#!") {
+            return None;
+        }
+
+        let end = beg
+            + rest
+                .iter()
+                .position(|&c| c == b'\r' || c == b'\n')
+                .unwrap_or(rest.len());
+        self.cursor = end;
+        if !self.trivia {
+            return None;
+        }
+        let s = core::str::from_utf8(&self.s[beg..end]).unwrap();
+        self.create_token(TokenType::SHEBANG(s), beg, end)
     }
 
     fn create_token(
@@ -101,8 +578,11 @@ impl<'a> Scanner<'a> {
         beginning_of_token: usize,
         end_of_token: usize,
     ) -> Option<Result<Token<'a>>> {
+        let beginning_of_token = self.base_index + beginning_of_token;
+        let end_of_token = self.base_index + end_of_token;
         Some(Ok(Token {
             token_type,
+            file_id: self.file_id,
             line_number: self.current_line_number,
             line_start_index: self.current_line_start_index,
             start_index_within_input: beginning_of_token,
@@ -117,7 +597,7 @@ impl<'a> Scanner<'a> {
         beginning_of_token: usize,
         end_of_token: usize,
     ) -> Option<Result<Token<'a>>> {
-        let s = std::str::from_utf8(&self.s[beginning_of_token..end_of_token]).unwrap();
+        let s = core::str::from_utf8(&self.s[beginning_of_token..end_of_token]).unwrap();
         self.create_token(
             TokenType::NUMBER(s.parse::<f64>().unwrap()),
             beginning_of_token,
@@ -130,34 +610,252 @@ impl<'a> Scanner<'a> {
         beginning_of_token: usize,
         end_of_token: usize,
     ) -> Option<Result<Token<'a>>> {
-        let s = std::str::from_utf8(&self.s[beginning_of_token..end_of_token]).unwrap();
+        let s = core::str::from_utf8(&self.s[beginning_of_token..end_of_token]).unwrap();
         self.create_token(TokenType::STRING(s), beginning_of_token, end_of_token)
     }
 
     fn create_identifier(&mut self, beg: usize, end: usize) -> Option<Result<Token<'a>>> {
-        let s = std::str::from_utf8(&self.s[beg..end]).unwrap();
+        let s = core::str::from_utf8(&self.s[beg..end]).unwrap();
         self.create_token(TokenType::IDENTIFIER(s), beg, end)
     }
 
+    fn create_comment(&mut self, beg: usize, end: usize) -> Option<Result<Token<'a>>> {
+        let s = core::str::from_utf8(&self.s[beg..end]).unwrap();
+        self.create_token(TokenType::COMMENT(s), beg, end)
+    }
+
+    // String with typographic quotation marks, for example “text”. Produces the string token,
+    // and an error for every quotation mark that isn't "\"".
+    fn create_smart_quoted_string(
+        &mut self,
+        open: usize,
+        beg: usize,
+        end: usize,
+        close: usize,
+    ) -> Option<Result<Token<'a>>> {
+        let open_error = self.create_confusable_error(open, beg);
+        let close_error = self.create_confusable_error(close, self.cursor);
+        let string = self.create_string_token(beg, end);
+        self.return_in_order([open_error, string, close_error])
+    }
+
+    // Raw string: r"...", r#"..."#, r##"..."## and so on. The cursor is after the opening quotation mark.
+    // Ends at the first quotation mark followed by as many "#" as the string started with. Only one line.
+    // If it isn't closed, the rest of the line belongs to it and the end of the line is reported.
+    fn create_raw_string(&mut self, beg: usize) -> Option<Result<Token<'a>>> {
+        let content_beg = self.cursor;
+        let hashes = content_beg - beg - 2;
+        let line = &self.s[content_beg..];
+        let line = &line[..line
+            .iter()
+            .position(|&c| c == b'\r' || c == b'\n')
+            .unwrap_or(line.len())];
+        let content_len = line.iter().enumerate().position(|(i, &c)| {
+            c == b'"' && line[i + 1..].iter().take_while(|&&c| c == b'#').count() >= hashes
+        });
+        let Some(content_len) = content_len else {
+            self.cursor = content_beg + line.len();
+            return self.unterminated_string_error(self.cursor, beg);
+        };
+
+        let content_end = content_beg + content_len;
+        self.cursor = content_end + 1 + hashes;
+        let s = core::str::from_utf8(&self.s[content_beg..content_end]).unwrap();
+        self.create_token(TokenType::RAW_STRING(s), content_beg, content_end)
+    }
+
+    // "text${": the beginning of an interpolated string, `beg..end` is the text. Code follows until the matching "}".
+    fn start_interpolated_string(&mut self, beg: usize, end: usize) -> Option<Result<Token<'a>>> {
+        let s = core::str::from_utf8(&self.s[beg..end]).unwrap();
+        let token = self.create_token(TokenType::STRING_START(s), beg, end);
+        if !self.push_interpolation(beg - 1) {
+            self.string_start = beg - 1;
+            return self.report_interpolation_overflow(token, end);
+        }
+        token
+    }
+
+    // "}text${" in `YYC_STR`: the text between two interpolations.
+    fn continue_interpolated_string(
+        &mut self,
+        beg: usize,
+        end: usize,
+    ) -> Option<Result<Token<'a>>> {
+        self.condition = YYC_INIT;
+        let s = core::str::from_utf8(&self.s[beg..end]).unwrap();
+        let token = self.create_token(TokenType::STRING_PART(s), beg, end);
+        if !self.push_interpolation(self.string_start) {
+            return self.report_interpolation_overflow(token, end);
+        }
+        token
+    }
+
+    // The "${" at `dollar` is nested too deep. It doesn't start an interpolation: the "$" is reported and the rest
+    // of the string, up to the closing quotation mark, is scanned as text, so the matching "}" is part of it.
+    fn report_interpolation_overflow(
+        &mut self,
+        token: Option<Result<Token<'a>>>,
+        dollar: usize,
+    ) -> Option<Result<Token<'a>>> {
+        self.condition = YYC_STR;
+        let error = self.create_unexpected_symbol_error(dollar);
+        self.return_in_order([token, error])
+    }
+
+    // "}text\"" in `YYC_STR`: the end of an interpolated string.
+    fn end_interpolated_string(&mut self, beg: usize, end: usize) -> Option<Result<Token<'a>>> {
+        self.condition = YYC_INIT;
+        let s = core::str::from_utf8(&self.s[beg..end]).unwrap();
+        self.create_token(TokenType::STRING_END(s), beg, end)
+    }
+
+    // Returns false if the interpolations are nested too deep.
+    fn push_interpolation(&mut self, string_start: usize) -> bool {
+        if self.interpolation_depth == MAX_INTERPOLATION_DEPTH {
+            return false;
+        }
+        self.interpolations[self.interpolation_depth] = Interpolation {
+            string_start,
+            open_braces: 0,
+        };
+        self.interpolation_depth += 1;
+        true
+    }
+
+    fn create_left_brace(&mut self, beg: usize, end: usize) -> Option<Result<Token<'a>>> {
+        if let Some(interpolation) = self.interpolations[..self.interpolation_depth].last_mut() {
+            interpolation.open_braces += 1;
+        }
+        self.create_token(TokenType::LEFT_BRACE, beg, end)
+    }
+
+    // Returns true if the "}" closes an interpolation rather than a brace opened inside of it.
+    // The scanner then continues with the rest of the string.
+    fn close_interpolation(&mut self) -> bool {
+        let Some(interpolation) = self.interpolations[..self.interpolation_depth].last_mut() else {
+            return false;
+        };
+        if interpolation.open_braces > 0 {
+            interpolation.open_braces -= 1;
+            return false;
+        }
+        self.string_start = interpolation.string_start;
+        self.interpolation_depth -= 1;
+        self.condition = YYC_STR;
+        true
+    }
+
+    // Drops all open interpolations. The scanner continues with code.
+    fn create_unterminated_string_error(
+        &mut self,
+        error_index_within_input: usize,
+    ) -> Option<Result<Token<'a>>> {
+        let string_start = match self.interpolation_depth {
+            0 => self.string_start,
+            _ => self.interpolations[0].string_start,
+        };
+        self.condition = YYC_INIT;
+        self.interpolation_depth = 0;
+        self.unterminated_string_error(error_index_within_input, string_start)
+    }
+
+    // The string that starts at `string_start` isn't closed at `error_index_within_input`.
+    fn unterminated_string_error(
+        &self,
+        error_index_within_input: usize,
+        string_start: usize,
+    ) -> Option<Result<Token<'a>>> {
+        let error_index_within_input = self.base_index + error_index_within_input;
+        Some(Err(UnterminatedStringError {
+            file_id: self.file_id,
+            line_number: self.current_line_number,
+            line_start_index_within_input: self.current_line_start_index,
+            error_index_within_line: error_index_within_input - self.current_line_start_index,
+            error_index_within_input,
+            string_start_index_within_input: self.base_index + string_start,
+        }
+        .into()))
+    }
+
+    // Any character that doesn't start a token. `beg..end` is a single UTF-8 sequence.
+    // The extension gets the first chance to turn it into a custom token.
+    fn handle_unexpected_character(&mut self, beg: usize, end: usize) -> Option<Result<Token<'a>>> {
+        if E::SCANS {
+            let src: &'a str = self.src;
+            let rest = &src[beg..];
+            if let Some((kind, len)) = self.extension.scan(rest) {
+                assert!(
+                    len > 0 && rest.is_char_boundary(len),
+                    "scanner extension returned an invalid length {len}"
+                );
+                assert!(
+                    !rest[..len].contains(['\r', '\n']),
+                    "scanner extension matched across a line break"
+                );
+                self.cursor = beg + len;
+                return self.create_token(TokenType::CUSTOM(kind, &rest[..len]), beg, self.cursor);
+            }
+        }
+
+        self.create_confusable_error(beg, end)
+            .or_else(|| self.create_unexpected_symbol_error(beg))
+    }
+
+    // Returns `None` if the character isn't a known confusable.
+    fn create_confusable_error(&self, beg: usize, end: usize) -> Option<Result<Token<'a>>> {
+        let symbol = core::str::from_utf8(&self.s[beg..end])
+            .ok()?
+            .chars()
+            .next()?;
+        let confusable = Confusable::find(symbol)?;
+        let error_index_within_input = self.base_index + beg;
+        Some(Err(ConfusableCharacterError {
+            file_id: self.file_id,
+            line_number: self.current_line_number,
+            line_start_index_within_input: self.current_line_start_index,
+            error_index_within_line: error_index_within_input - self.current_line_start_index,
+            error_index_within_input,
+            error_end_index_within_input: self.base_index + end,
+            symbol,
+            name: confusable.name,
+            suggestion: confusable.suggestion,
+        }
+        .into()))
+    }
+
     fn create_unexpected_symbol_error(
         &self,
         error_index_within_input: usize,
     ) -> Option<Result<Token<'a>>> {
-        Some(Err(anyhow::Error::new(UnexpectedSymbolError {
+        let error_index_within_input = self.base_index + error_index_within_input;
+        Some(Err(UnexpectedSymbolError {
+            file_id: self.file_id,
             line_number: self.current_line_number,
             line_start_index_within_input: self.current_line_start_index,
             error_index_within_line: error_index_within_input - self.current_line_start_index,
             error_index_within_input,
-        })))
+        }
+        .into()))
     }
 }
 
-impl<'a> Iterator for Scanner<'a> {
+impl<'a, E: ScannerExtension> Iterator for Scanner<'a, E> {
     type Item = Result<Token<'a>>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.pending[0].take() {
+            self.pending.rotate_left(1);
+            return Some(item);
+        }
+        if self.at_start {
+            self.at_start = false;
+            if let Some(item) = self.scan_shebang() {
+                return Some(item);
+            }
+        }
+
         /*!svars:re2c format = '#[allow(unused_mut)] let mut @@;'; */
-        /*!stags:re2c format = 'let mut @@ = std::usize::MAX;'; */
+        /*!stags:re2c format = 'let mut @@ = usize::MAX;'; */
 
         'lex: loop { /*!local:re2c
             re2c:encoding:utf8 = 1;
@@ -176,73 +874,96 @@ impl<'a> Iterator for Scanner<'a> {
             re2c:YYLESSTHAN   = "self.s.len() <= self.cursor";
             re2c:YYSHIFT      = "self.cursor = (self.cursor as isize + @@{shift}) as usize;";
             re2c:YYSTAGP      = "@@{tag} = self.cursor;";
-            re2c:YYSTAGN      = "@@{tag} = std::usize::MAX;";
+            re2c:YYSTAGN      = "@@{tag} = usize::MAX;";
             re2c:YYSHIFTSTAG  = "@@{tag} = (@@{tag} as isize + @@{shift}) as usize;";
 
-            // New lines. Update the line number and line start index
-            "\r\n" | "\n\r" | "\r" | "\n"           { self.current_line_number += 1; self.current_line_start_index = self.cursor; continue 'lex; }
+            re2c:YYGETCONDITION = "self.condition";
+            re2c:YYSETCONDITION = "self.condition = @@;";
+
+            // Text of a string: anything up to the end of the line, the closing quotation mark or an interpolation
+            strcontent = ([^\r\n"$] | "$"+ [^\r\n"{$])* "$"*;
+
+            // New lines. Whether they update the line number and line start index depends on the newline policy
+            <INIT> @beg ("\r\n" | "\n\r" | "\r" | "\n") @end {
+                if let Some(error) = self.handle_line_ending(beg, end) {
+                    return Some(error);
+                }
+                continue 'lex;
+            }
 
             // Skip whitespace and tabs
-            [\t ]+                                  { continue 'lex;}
+            <INIT> @beg [\t ]+                 @end {
+                if self.trivia {
+                    return self.create_token(TokenType::WHITESPACE, beg, end);
+                }
+                continue 'lex;
+            }
 
             // Skip comments
-            "//" [^\r\n]*                           { continue 'lex; }
+            <INIT> @beg "//" [^\r\n]*          @end {
+                if self.trivia {
+                    return self.create_comment(beg, end);
+                }
+                continue 'lex;
+            }
 
             // Numbers. Following formats are supported: "123", "123.456". Not supported: ".123" or "123."
-            @beg [0-9]+ ("." [0-9]+)?          @end { return self.create_number_token(beg, end) }
+            <INIT> @beg [0-9]+ ("." [0-9]+)?   @end { return self.create_number_token(beg, end) }
 
             // Strings. Only one line. Not allowed: escape sequences like "\n" inside the string.
             // We save the string without quotation marks.
-            ["] @beg [^\r\n"]* @end ["]              { return self.create_string_token(beg, end) }
-
-            // Tokens
-            @beg "("                           @end { return self.create_token(TokenType::LEFT_PAREN, beg, end) }
-            @beg ")"                           @end { return self.create_token(TokenType::RIGHT_PAREN, beg, end) }
-            @beg "{"                           @end { return self.create_token(TokenType::LEFT_BRACE, beg, end) }
-            @beg "}"                           @end { return self.create_token(TokenType::RIGHT_BRACE, beg, end) }
-            @beg ","                           @end { return self.create_token(TokenType::COMMA, beg, end) }
-            @beg "."                           @end { return self.create_token(TokenType::DOT, beg, end) }
-            @beg "-"                           @end { return self.create_token(TokenType::MINUS, beg, end) }
-            @beg "+"                           @end { return self.create_token(TokenType::PLUS, beg, end) }
-            @beg ";"                           @end { return self.create_token(TokenType::SEMICOLON, beg, end) }
-            @beg "*"                           @end { return self.create_token(TokenType::STAR, beg, end) }
-            @beg "!="                          @end { return self.create_token(TokenType::BANG_EQUAL, beg, end) }
-            @beg "!"                           @end { return self.create_token(TokenType::BANG, beg, end) }
-            @beg "=="                          @end { return self.create_token(TokenType::EQUAL_EQUAL, beg, end) }
-            @beg "="                           @end { return self.create_token(TokenType::EQUAL, beg, end) }
-            @beg ">="                          @end { return self.create_token(TokenType::GREATER_EQUAL, beg, end) }
-            @beg ">"                           @end { return self.create_token(TokenType::GREATER, beg, end) }
-            @beg "<="                          @end { return self.create_token(TokenType::LESS_EQUAL, beg, end) }
-            @beg "<"                           @end { return self.create_token(TokenType::LESS, beg, end) }
-            @beg "/"                           @end { return self.create_token(TokenType::SLASH, beg, end) }
-            @beg "and"                         @end { return self.create_token(TokenType::AND, beg, end) }
-            @beg "class"                       @end { return self.create_token(TokenType::CLASS, beg, end) }
-            @beg "else"                        @end { return self.create_token(TokenType::ELSE, beg, end) }
-            @beg "false"                       @end { return self.create_token(TokenType::FALSE, beg, end) }
-            @beg "true"                        @end { return self.create_token(TokenType::TRUE, beg, end) }
-            @beg "fun"                         @end { return self.create_token(TokenType::FUN, beg, end) }
-            @beg "for"                         @end { return self.create_token(TokenType::FOR, beg, end) }
-            @beg "if"                          @end { return self.create_token(TokenType::IF, beg, end) }
-            @beg "nil"                         @end { return self.create_token(TokenType::NIL, beg, end) }
-            @beg "or"                          @end { return self.create_token(TokenType::OR, beg, end) }
-            @beg "print"                       @end { return self.create_token(TokenType::PRINT, beg, end) }
-            @beg "return"                      @end { return self.create_token(TokenType::RETURN, beg, end) }
-            @beg "super"                       @end { return self.create_token(TokenType::SUPER, beg, end) }
-            @beg "this"                        @end { return self.create_token(TokenType::THIS, beg, end) }
-            @beg "var"                         @end { return self.create_token(TokenType::VAR, beg, end) }
-            @beg "while"                       @end { return self.create_token(TokenType::WHILE, beg, end) }
+            <INIT> ["] @beg strcontent @end ["]       { return self.create_string_token(beg, end) }
+
+            // Strings with interpolations, like "Hello ${name}!". The text up to the first interpolation.
+            // The rest of the string is scanned in the `STR` condition once the interpolation is closed.
+            <INIT> ["] @beg strcontent @end "${"      { return self.start_interpolated_string(beg, end) }
+            <STR> @beg strcontent @end "${"           { return self.continue_interpolated_string(beg, end) }
+            <STR> @beg strcontent @end ["]            { return self.end_interpolated_string(beg, end) }
+
+            // The rest of the line doesn't continue the string. Scanned again as code.
+            <STR> * {
+                self.cursor -= 1;
+                return self.create_unterminated_string_error(self.cursor)
+            }
+            <STR> $                                   { return self.create_unterminated_string_error(self.s.len()) }
+
+            // Strings with typographic quotation marks, pasted from a word processor. Reported as errors,
+            // but still produce a string token. A plain string of the same length wins, because it is listed first.
+            <INIT> @open ["\u201C\u201D\u201E] @beg ([^\r\n"$\u201C\u201D\u201E] | "$"+ [^\r\n"{$\u201C\u201D\u201E])* "$"* @end @close ["\u201C\u201D\u201E] {
+                return self.create_smart_quoted_string(open, beg, end, close)
+            }
+
+            // Raw strings, like r"C:\path" or r#"say "hi""#. The rest is found by `create_raw_string`
+            <INIT> @beg "r" "#"* ["]                  { return self.create_raw_string(beg) }
+
+            // Braces. Inside of an interpolation the scanner counts them to find the "}" that closes it
+            <INIT> @beg "{" @end                      { return self.create_left_brace(beg, end) }
+            <INIT> @beg "}" @end {
+                if self.close_interpolation() {
+                    continue 'lex;
+                }
+                return self.create_token(TokenType::RIGHT_BRACE, beg, end)
+            }
+
+            // Keywords and the other punctuators, generated by build.rs from `token_spec.rs`
+            !include "tokens.re";
 
             // Identifiers. For example: "var123", "_var", "var_123"
-            @beg [A-Za-z_][A-Za-z0-9_]*        @end { return self.create_identifier(beg, end) }
+            <INIT> @beg [A-Za-z_][A-Za-z0-9_]* @end { return self.create_identifier(beg, end) }
 
-            // Any other character is an error
-            .                                       { return self.create_unexpected_symbol_error(self.cursor - 1) }
+            // Any other character is an error. Characters that look like Lox symbols get a specific error.
+            <INIT> @beg .                      @end { return self.handle_unexpected_character(beg, end) }
 
             // Catch ill-formed UTF-8 or orphan bytes
-            *                                       { return self.create_unexpected_symbol_error(self.cursor - 1) }
+            <INIT> *                                { return self.create_unexpected_symbol_error(self.cursor - 1) }
 
-            // End of input
-            $                                       { return None; } */
+            // End of input. An interpolation that is still open ends the string there
+            <INIT> $ {
+                if self.interpolation_depth > 0 {
+                    return self.create_unterminated_string_error(self.s.len());
+                }
+                return None;
+            } */
         }
     }
 }
@@ -0,0 +1,233 @@
+use crate::lox_language_scanner::{NewlinePolicy, Scanner, SourceOrigin};
+
+// Fenced code block with Lox code inside of a Markdown document:
+//
+// ```lox
+// print "hello";
+// ```
+pub struct LoxBlock<'a> {
+    // Content of the block, without the fences.
+    pub code: &'a str,
+    // Position of the content within the Markdown document.
+    pub origin: SourceOrigin,
+}
+
+impl<'a> LoxBlock<'a> {
+    pub fn scanner(&self) -> Scanner<'a> {
+        Scanner::with_origin(self.code, self.origin)
+    }
+}
+
+struct Fence {
+    symbol: u8,
+    len: usize,
+}
+
+struct OpenBlock {
+    fence: Fence,
+    is_lox: bool,
+    content_start: usize,
+    content_line_number: usize,
+}
+
+// Finds all ```lox (or ~~~lox) fenced code blocks. A block that isn't closed runs until the end of the document.
+// Lines are split like the scanner splits them, so that line numbers within the blocks agree with the document.
+pub fn lox_blocks(markdown: &str) -> Vec<LoxBlock<'_>> {
+    let mut blocks = Vec::new();
+    let mut open: Option<OpenBlock> = None;
+
+    let s = markdown.as_bytes();
+    let line_break_len = |i: usize| NewlinePolicy::default().line_break_len(&s[i..]);
+    // The first line starts after the byte order mark, like in the scanner
+    let mut line_start = if markdown.starts_with('\u{feff}') {
+        '\u{feff}'.len_utf8()
+    } else {
+        0
+    };
+    let mut line_number = 0;
+    while line_start < s.len() {
+        let text_end = (line_start..s.len())
+            .find(|&i| line_break_len(i) > 0)
+            .unwrap_or(s.len());
+        let next_line_start = text_end + line_break_len(text_end);
+        let text = &markdown[line_start..text_end];
+
+        match &open {
+            None => {
+                if let Some((fence, info)) = parse_fence(text) {
+                    open = Some(OpenBlock {
+                        fence,
+                        is_lox: info.split_whitespace().next() == Some("lox"),
+                        content_start: next_line_start,
+                        content_line_number: line_number + 1,
+                    });
+                }
+            }
+            Some(block) => {
+                if is_closing_fence(text, &block.fence) {
+                    if block.is_lox {
+                        blocks.push(lox_block(markdown, block, line_start));
+                    }
+                    open = None;
+                }
+            }
+        }
+
+        line_start = next_line_start;
+        line_number += 1;
+    }
+
+    if let Some(block) = open
+        && block.is_lox
+    {
+        blocks.push(lox_block(markdown, &block, markdown.len()));
+    }
+
+    blocks
+}
+
+// Scans every Lox block and returns the scanning errors. The errors point into the Markdown document.
+pub fn scan_errors(markdown: &str) -> Vec<anyhow::Error> {
+    lox_blocks(markdown)
+        .iter()
+        .flat_map(|block| block.scanner().filter_map(Result::err))
+        .collect()
+}
+
+fn lox_block<'a>(markdown: &'a str, block: &OpenBlock, content_end: usize) -> LoxBlock<'a> {
+    LoxBlock {
+        code: &markdown[block.content_start..content_end],
+        origin: SourceOrigin {
+            byte_offset: block.content_start,
+            line_number: block.content_line_number,
+            line_start_index: block.content_start,
+            ..SourceOrigin::default()
+        },
+    }
+}
+
+// An opening fence is at least three backticks or tildes, indented by at most three spaces,
+// followed by an info string. Returns the fence and the info string.
+fn parse_fence(line: &str) -> Option<(Fence, &str)> {
+    let (fence, rest) = fence_prefix(line)?;
+    // Backtick fences can't have backticks in the info string.
+    if fence.symbol == b'`' && rest.contains('`') {
+        return None;
+    }
+    Some((fence, rest.trim()))
+}
+
+// A closing fence uses the same symbol, is at least as long as the opening one and has nothing after it.
+fn is_closing_fence(line: &str, opening: &Fence) -> bool {
+    match fence_prefix(line) {
+        Some((fence, rest)) => {
+            fence.symbol == opening.symbol && fence.len >= opening.len && rest.trim().is_empty()
+        }
+        None => false,
+    }
+}
+
+fn fence_prefix(line: &str) -> Option<(Fence, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+
+    let line = &line[indent..];
+    let symbol = *line.as_bytes().first()?;
+    if symbol != b'`' && symbol != b'~' {
+        return None;
+    }
+
+    let len = line.bytes().take_while(|&b| b == symbol).count();
+    if len < 3 {
+        return None;
+    }
+
+    Some((Fence { symbol, len }, &line[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox_language_scanner::UnexpectedSymbolError;
+
+    const DOC: &str = "# Title\n\
+                       \n\
+                       ```lox\n\
+                       var x = 1;\n\
+                       ```\n\
+                       \n\
+                       ```rust\n\
+                       let x = @1;\n\
+                       ```\n\
+                       \n\
+                       ~~~~ lox linenums\n\
+                       ```\n\
+                       print @x;\n\
+                       ~~~~\n";
+
+    #[test]
+    fn finds_lox_blocks_only() {
+        let blocks = lox_blocks(DOC);
+        assert_eq!(blocks.len(), 2);
+
+        assert_eq!(blocks[0].code, "var x = 1;\n");
+        assert_eq!(blocks[0].origin.line_number, 3);
+        assert_eq!(blocks[0].origin.byte_offset, DOC.find("var").unwrap());
+
+        // A fence made of another symbol doesn't close the block.
+        assert_eq!(blocks[1].code, "```\nprint @x;\n");
+        assert_eq!(blocks[1].origin.line_number, 11);
+    }
+
+    #[test]
+    fn unclosed_block_runs_until_end_of_document() {
+        let blocks = lox_blocks("```lox\r\nprint 1;\r\n");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].code, "print 1;\r\n");
+        assert_eq!(blocks[0].origin.line_number, 1);
+    }
+
+    #[test]
+    fn lines_are_split_like_in_the_scanner() {
+        let doc = "\u{feff}```lox\rprint 1;\r\n\rprint @;\r```\r";
+        let blocks = lox_blocks(doc);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].code, "print 1;\r\n\rprint @;\r");
+        assert_eq!(blocks[0].origin.line_number, 1);
+
+        let errors = scan_errors(doc);
+        let err = errors[0].downcast_ref::<UnexpectedSymbolError>().unwrap();
+        assert_eq!(err.line_number, 3);
+        assert_eq!(err.error_index_within_input, doc.find('@').unwrap());
+    }
+
+    #[test]
+    fn errors_point_into_markdown_document() {
+        let errors = scan_errors(DOC);
+        let positions: Vec<_> = errors
+            .iter()
+            .map(|err| {
+                let err = err.downcast_ref::<UnexpectedSymbolError>().unwrap();
+                (
+                    err.line_number,
+                    err.error_index_within_line,
+                    err.error_index_within_input,
+                )
+            })
+            .collect();
+
+        // "```" inside of the tilde block is not Lox, and neither is "@".
+        let backticks = DOC.find("```\nprint").unwrap();
+        assert_eq!(
+            positions,
+            [
+                (11, 0, backticks),
+                (11, 1, backticks + 1),
+                (11, 2, backticks + 2),
+                (12, 6, DOC.find("@x").unwrap()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use crate::lox_language_scanner::{Scanner, Token, TokenKind, TokenType};
+use crate::token_printer::write_token;
+
+// Names that have a meaning besides the identifier itself: the class initializer and the native function of the
+// interpreter from the book. They are never renamed.
+const RESERVED_NAMES: &[&str] = &["init", "clock"];
+
+// Makes Lox source as small as possible without changing the tokens: comments and line breaks are dropped,
+// numbers are printed in their shortest form and a single space is only written where two tokens would merge.
+//
+// let small = Minifier::new().rename_identifiers(true).keep_name("main").minify(src)?;
+#[derive(Debug, Clone, Default)]
+pub struct Minifier<'a> {
+    rename_identifiers: bool,
+    kept_names: Vec<&'a str>,
+}
+
+impl<'a> Minifier<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // When enabled, every identifier is replaced with a short name, the most frequent ones get the shortest names.
+    // The renaming doesn't know about scopes: all occurrences of a name get the same new name, which keeps the
+    // program correct as long as it doesn't refer to names defined outside of it.
+    pub fn rename_identifiers(mut self, rename: bool) -> Self {
+        self.rename_identifiers = rename;
+        self
+    }
+
+    // Name that isn't renamed, for example a function the host calls or a native function it provides.
+    pub fn keep_name(mut self, name: &'a str) -> Self {
+        self.kept_names.push(name);
+        self
+    }
+
+    // A shebang line is kept. Code with scanning errors isn't minified, since the result would scan differently;
+    // the errors are returned instead.
+    pub fn minify(&self, src: &str) -> Result<String, Vec<anyhow::Error>> {
+        // With trivia, so that the scanner finds the shebang, also after a byte order mark
+        let (tokens, errors): (Vec<_>, Vec<_>) = Scanner::new(src)
+            .skip_shebang(true)
+            .trivia(true)
+            .partition(Result::is_ok);
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(Result::unwrap_err).collect());
+        }
+        let mut tokens: Vec<_> = tokens.into_iter().map(Result::unwrap).collect();
+        let shebang = tokens.iter().find_map(|token| match token.token_type {
+            TokenType::SHEBANG(text) => Some(text),
+            _ => None,
+        });
+        tokens.retain(|token| !token.token_type.kind().is_trivia());
+
+        let renames = if self.rename_identifiers {
+            self.short_names(&tokens)
+        } else {
+            HashMap::new()
+        };
+        let texts: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                let mut text = String::new();
+                match token.token_type {
+                    TokenType::IDENTIFIER(name) => {
+                        text.push_str(renames.get(name).map_or(name, String::as_str))
+                    }
+                    // Too large for `f64`, there is no shorter form that is still the same number
+                    TokenType::NUMBER(value) if value.is_infinite() => {
+                        text.push_str(token.lexeme(src))
+                    }
+                    ref token_type => write_token(&mut text, token_type),
+                }
+                text
+            })
+            .collect();
+
+        let mut out = String::with_capacity(src.len());
+        if let Some(shebang) = shebang {
+            out.push_str(shebang);
+            out.push('\n');
+        }
+        for (i, text) in texts.iter().enumerate() {
+            if i > 0 && needs_space(&tokens, &texts, i) {
+                out.push(' ');
+            }
+            out.push_str(text);
+        }
+        Ok(out)
+    }
+
+    // New names for all identifiers that aren't kept, shortest names for the most frequent identifiers.
+    fn short_names(&self, tokens: &[Token]) -> HashMap<String, String> {
+        let is_kept =
+            |name: &str| RESERVED_NAMES.contains(&name) || self.kept_names.contains(&name);
+
+        // Count, and the index of the first occurrence, so that the order doesn't depend on the hash map
+        let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if let TokenType::IDENTIFIER(name) = token.token_type
+                && !is_kept(name)
+            {
+                counts.entry(name).or_insert((0, i)).0 += 1;
+            }
+        }
+        let mut names: Vec<_> = counts.into_iter().collect();
+        names.sort_by_key(|&(_, (count, first))| (usize::MAX - count, first));
+
+        let is_keyword = |name: &str| {
+            TokenKind::ALL
+                .iter()
+                .any(|kind| kind.fixed_lexeme() == Some(name))
+        };
+        let mut short_names = (0..)
+            .map(short_name)
+            .filter(|name| !is_keyword(name) && !is_kept(name));
+        names
+            .into_iter()
+            .map(|(name, _)| (name.to_string(), short_names.next().unwrap()))
+            .collect()
+    }
+}
+
+// The `n`-th shortest identifier: "a" to "_", then "aa", "ba" and so on. The first character can't be a digit.
+fn short_name(mut n: usize) -> String {
+    const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_";
+    const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_0123456789";
+    let mut name = String::from(FIRST[n % FIRST.len()] as char);
+    n /= FIRST.len();
+    while n > 0 {
+        n -= 1;
+        name.push(REST[n % REST.len()] as char);
+        n /= REST.len();
+    }
+    name
+}
+
+// Whether the tokens `i - 1` and `i` would be scanned differently if they were written without a space.
+fn needs_space(tokens: &[Token], texts: &[String], i: usize) -> bool {
+    let (prev, next) = (texts[i - 1].as_str(), texts[i].as_str());
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let kind = |i: usize| tokens.get(i).map(|token| token.token_type.kind());
+    // `var x`, `a or b`
+    prev.ends_with(is_word) && next.starts_with(is_word)
+        // `1 .5` would be `1.5`, while `1.x` is fine
+        || kind(i - 1) == Some(TokenKind::NUMBER)
+            && kind(i) == Some(TokenKind::DOT)
+            && kind(i + 1) == Some(TokenKind::NUMBER)
+        // `/` followed by `/` starts a comment
+        || prev.ends_with('/') && next.starts_with('/')
+        // `r` followed by a string is a raw string
+        || prev == "r" && next.starts_with('"')
+        // `=` followed by `==` would be `==` `=`
+        || TokenKind::ALL
+            .iter()
+            .filter_map(|kind| kind.fixed_lexeme())
+            .any(|lexeme| lexeme.len() > prev.len() && (prev.to_string() + next).starts_with(lexeme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_types(src: &str) -> Vec<TokenType<'_>> {
+        Scanner::new(src)
+            .skip_shebang(true)
+            .map(|r| r.unwrap().token_type)
+            .collect()
+    }
+
+    #[test]
+    fn writes_spaces_only_where_tokens_would_merge() {
+        let src = "#!/usr/bin/env lox\n// comment\nvar x = 1.50;\nif (a or b) print 1 . 5 / / 2;\nprint x.y = == r \"s\";";
+        let minified = Minifier::new().minify(src).unwrap();
+        assert_eq!(
+            minified,
+            "#!/usr/bin/env lox\nvar x=1.5;if(a or b)print 1 .5/ /2;print x.y= ==r \"s\";"
+        );
+        assert_eq!(token_types(&minified), token_types(src));
+    }
+
+    #[test]
+    fn shebang_after_byte_order_mark_is_kept() {
+        let src = "\u{feff}#!/usr/bin/env lox\rprint 1;";
+        let minified = Minifier::new().minify(src).unwrap();
+        assert_eq!(minified, "#!/usr/bin/env lox\nprint 1;");
+        assert_eq!(token_types(&minified), token_types(src));
+    }
+
+    #[test]
+    fn interpolated_and_raw_strings_keep_their_tokens() {
+        let src = "print \"a${ {x}.y }b${ \"c${ d }\" }e\" + r#\"say \"hi\"\"# ;";
+        let minified = Minifier::new().minify(src).unwrap();
+        assert_eq!(
+            minified,
+            "print\"a${{x}.y}b${\"c${d}\"}e\"+r#\"say \"hi\"\"#;"
+        );
+        assert_eq!(token_types(&minified), token_types(src));
+    }
+
+    #[test]
+    fn numbers_too_large_for_f64_keep_their_lexeme() {
+        let number = "9".repeat(400);
+        let src = format!("print {number} + 1.0;");
+        let minified = Minifier::new().minify(&src).unwrap();
+        assert_eq!(minified, format!("print {number}+1;"));
+        assert_eq!(token_types(&minified), token_types(&src));
+    }
+
+    #[test]
+    fn renames_frequent_identifiers_to_shorter_names() {
+        let src = "class Point { init(x) { this.x = x; } } var point = Point(clock()); print point.x + x;";
+        let minified = Minifier::new()
+            .rename_identifiers(true)
+            .keep_name("Point")
+            .minify(src)
+            .unwrap();
+        assert_eq!(
+            minified,
+            "class Point{init(a){this.a=a;}}var b=Point(clock());print b.a+a;"
+        );
+    }
+
+    // Some of the names are keywords, like "if". `Minifier` skips them.
+    #[test]
+    fn short_names_are_unique_and_scan_as_a_single_word() {
+        let names: Vec<_> = (0..10_000).map(short_name).collect();
+        assert_eq!(&names[..3], ["a", "b", "c"]);
+        assert_eq!(&names[53..55], ["aa", "ba"]);
+        for name in &names {
+            let tokens: Vec<_> = Scanner::new(name).map(|r| r.unwrap()).collect();
+            assert_eq!(tokens.len(), 1, "{name}");
+            assert_eq!(tokens[0].lexeme(name), name);
+        }
+        let unique: std::collections::HashSet<_> = names.iter().collect();
+        assert_eq!(unique.len(), names.len());
+    }
+}
@@ -0,0 +1,180 @@
+use std::thread;
+
+use anyhow::Result;
+
+use crate::lox_language_scanner::{NewlinePolicy, Scanner, SourceOrigin, Token};
+
+// Splitting only pays off when every thread gets a decent amount of work.
+const MIN_CHUNK_LEN: usize = 64 * 1024;
+
+// Scans the input on up to `threads` threads and returns exactly what the sequential `Scanner` would return.
+//
+// Lox strings and comments cannot span lines, so every complete line break is a token boundary and
+// the scanner is in its initial state right after it. The input is cut at such boundaries and
+// each chunk is scanned on its own thread, as a fragment of the whole input that starts on a known line.
+pub fn scan_parallel(src: &str, threads: usize) -> Vec<Result<Token<'_>>> {
+    let boundaries = split_points(src.as_bytes(), threads.max(1));
+    if boundaries.len() <= 2 {
+        return Scanner::new(src).collect();
+    }
+
+    // Counting the line breaks is much cheaper than scanning, so it is done up front
+    let mut line_number = 0;
+    let origins: Vec<_> = boundaries
+        .windows(2)
+        .map(|window| {
+            let beg = window[0];
+            let origin = SourceOrigin {
+                byte_offset: beg,
+                line_number,
+                // Every chunk starts right after a line break
+                line_start_index: beg,
+                ..SourceOrigin::default()
+            };
+            line_number += count_line_breaks(&src.as_bytes()[beg..window[1]]);
+            origin
+        })
+        .collect();
+
+    let chunks: Vec<Vec<_>> = thread::scope(|scope| {
+        let handles: Vec<_> = boundaries
+            .windows(2)
+            .zip(origins)
+            .map(|(window, origin)| {
+                let chunk = &src[window[0]..window[1]];
+                scope.spawn(move || Scanner::with_origin(chunk, origin).collect())
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("scanner thread panicked"))
+            .collect()
+    });
+
+    chunks.into_iter().flatten().collect()
+}
+
+// Line breaks of the scanner with its default `NewlinePolicy::Any`.
+fn count_line_breaks(s: &[u8]) -> usize {
+    let (mut i, mut count) = (0, 0);
+    while i < s.len() {
+        match NewlinePolicy::Any.line_break_len(&s[i..]) {
+            0 => i += 1,
+            len => {
+                i += len;
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+// Returns the chunk boundaries, including 0 and the input length.
+fn split_points(s: &[u8], threads: usize) -> Vec<usize> {
+    let chunk_count = threads.min(s.len() / MIN_CHUNK_LEN).max(1);
+    let mut points = vec![0];
+    for i in 1..chunk_count {
+        let last = *points.last().unwrap();
+        let target = (s.len() / chunk_count * i).max(last);
+        if let Some(point) = next_safe_boundary(s, target)
+            && point > last
+        {
+            points.push(point);
+        }
+    }
+    points.push(s.len());
+    points.dedup();
+    points
+}
+
+// A position is safe when it directly follows a complete line break.
+// "\r\n" and "\n\r" are single line breaks, so we must not cut them in two.
+fn next_safe_boundary(s: &[u8], from: usize) -> Option<usize> {
+    (from.max(1)..s.len()).find(|&i| match s[i - 1] {
+        b'\n' => s[i] != b'\r',
+        b'\r' => s[i] != b'\n',
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox_language_scanner::{
+        ConfusableCharacterError, UnexpectedSymbolError, UnterminatedStringError,
+    };
+
+    fn assert_same_as_sequential(src: &str, threads: usize) {
+        let sequential: Vec<_> = Scanner::new(src).collect();
+        let parallel = scan_parallel(src, threads);
+        assert_eq!(parallel.len(), sequential.len());
+
+        for (p, s) in parallel.iter().zip(sequential.iter()) {
+            match (p, s) {
+                (Ok(p), Ok(s)) => assert_eq!(p, s),
+                (Err(p), Err(s)) => {
+                    assert_eq!(
+                        p.downcast_ref::<UnexpectedSymbolError>(),
+                        s.downcast_ref::<UnexpectedSymbolError>()
+                    );
+                    assert_eq!(
+                        p.downcast_ref::<ConfusableCharacterError>(),
+                        s.downcast_ref::<ConfusableCharacterError>()
+                    );
+                    assert_eq!(
+                        p.downcast_ref::<UnterminatedStringError>(),
+                        s.downcast_ref::<UnterminatedStringError>()
+                    );
+                }
+                _ => panic!("Parallel result {:?} differs from sequential {:?}", p, s),
+            }
+        }
+    }
+
+    fn big_source() -> String {
+        let lines = [
+            "var x = 42; // comment\n",
+            "print \"hello, world\";\r\n",
+            "if (x >= 3.14) { x = x + 1; } @\n\r",
+            "fun f(a, b) { return a and b; }\r",
+            "\"unterminated string\n",
+            "print \"a${ b \"c${ d }\" }\" + \"open ${ x\n",
+            "r#\"unterminated raw string\"\n",
+            "print \u{201C}smart quotes\u{201D} \u{2013} 1\u{FF1B}\n",
+        ];
+        lines.iter().cycle().take(12_000).copied().collect()
+    }
+
+    #[test]
+    fn output_is_identical_to_sequential_scanner() {
+        let src = big_source();
+        assert!(src.len() > MIN_CHUNK_LEN * 4);
+        for threads in [1, 2, 3, 4] {
+            assert_same_as_sequential(&src, threads);
+        }
+    }
+
+    // Only a byte order mark at the beginning of the whole input is skipped, not one at the beginning of a chunk.
+    #[test]
+    fn byte_order_mark_is_only_skipped_at_the_beginning() {
+        let src = "\u{feff}print 1;\n".repeat(25_000);
+        assert!(src.len() > MIN_CHUNK_LEN * 4);
+        assert_same_as_sequential(&src, 4);
+    }
+
+    #[test]
+    fn small_inputs_are_scanned_on_one_thread() {
+        assert_eq!(split_points(b"var x = 1;\nvar y = 2;", 4), vec![0, 21]);
+        assert_same_as_sequential("var x = 1;\nvar y = 2;", 4);
+        assert_same_as_sequential("", 4);
+    }
+
+    #[test]
+    fn line_breaks_are_not_cut_in_two() {
+        assert_eq!(next_safe_boundary(b"a\r\nb", 2), Some(3));
+        assert_eq!(next_safe_boundary(b"a\n\rb", 2), Some(3));
+        assert_eq!(next_safe_boundary(b"a\n\r\nb", 2), Some(4));
+        assert_eq!(next_safe_boundary(b"abc", 0), None);
+    }
+}
@@ -0,0 +1,320 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::lox_language_scanner::{
+    ConfusableCharacterError, FileId, LineEnding, MixedLineEndingsError, NewlinePolicy, Scanner,
+    SourceOrigin, Token, UnexpectedSymbolError, UnterminatedStringError,
+};
+
+// Byte range within one of the files of a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file_id: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token<'_> {
+    pub fn span(&self) -> Span {
+        Span {
+            file_id: self.file_id,
+            start: self.start_index_within_input,
+            end: self.end_index_within_input,
+        }
+    }
+}
+
+impl UnexpectedSymbolError {
+    // Errors point at a single position, so the span is empty.
+    pub fn span(&self) -> Span {
+        Span {
+            file_id: self.file_id,
+            start: self.error_index_within_input,
+            end: self.error_index_within_input,
+        }
+    }
+}
+
+impl ConfusableCharacterError {
+    // Covers the confusable character, which is also the range to replace.
+    pub fn span(&self) -> Span {
+        Span {
+            file_id: self.file_id,
+            start: self.error_index_within_input,
+            end: self.error_end_index_within_input,
+        }
+    }
+}
+
+impl UnterminatedStringError {
+    // Covers the string from its opening quotation mark to where it should have been closed.
+    pub fn span(&self) -> Span {
+        Span {
+            file_id: self.file_id,
+            start: self.string_start_index_within_input,
+            end: self.error_index_within_input,
+        }
+    }
+}
+
+impl MixedLineEndingsError {
+    // Covers the line ending that differs from the first one.
+    pub fn span(&self) -> Span {
+        let len = match self.found {
+            LineEnding::CrLf | LineEnding::LfCr => 2,
+            LineEnding::Lf | LineEnding::Cr => 1,
+        };
+        Span {
+            file_id: self.file_id,
+            start: self.error_index_within_input,
+            end: self.error_index_within_input + len,
+        }
+    }
+}
+
+// Human readable position. Line and column numbers are 0-based, like everywhere in the scanner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location<'a> {
+    pub file_name: &'a str,
+    pub line_number: usize,
+    pub column: usize,
+}
+
+pub struct SourceFile {
+    name: String,
+    src: String,
+    // Offsets of the beginnings of all lines.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+}
+
+// Owns all source files of a program and hands out a `FileId` for each of them.
+// Every token and error is tagged with the id of the file it was scanned from,
+// so positions from different files can't be confused.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    newline_policy: NewlinePolicy,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Line breaks of all files, both for scanning and for `lookup`. Set it before adding files.
+    pub fn newline_policy(mut self, policy: NewlinePolicy) -> Self {
+        self.newline_policy = policy;
+        self
+    }
+
+    pub fn add_file(&mut self, name: impl Into<String>, src: impl Into<String>) -> FileId {
+        // `FileId(0)` is reserved for input that doesn't come from a source map
+        let id = FileId(u32::try_from(self.files.len() + 1).expect("too many source files"));
+        let src = src.into();
+        self.files.push(SourceFile {
+            name: name.into(),
+            line_starts: line_starts(&src, self.newline_policy),
+            src,
+        });
+        id
+    }
+
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<FileId> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(self.add_file(path.display().to_string(), src))
+    }
+
+    pub fn file(&self, file_id: FileId) -> &SourceFile {
+        let index = (file_id.0 as usize)
+            .checked_sub(1)
+            .expect("FileId(0) belongs to input scanned without a source map");
+        &self.files[index]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (FileId(i as u32 + 1), file))
+    }
+
+    pub fn scanner(&self, file_id: FileId) -> Scanner<'_> {
+        Scanner::with_origin(
+            self.file(file_id).src(),
+            SourceOrigin {
+                file_id,
+                ..SourceOrigin::default()
+            },
+        )
+        .newline_policy(self.newline_policy)
+    }
+
+    // Finds the file, line and column of the beginning of the span.
+    pub fn lookup(&self, span: Span) -> Location<'_> {
+        let file = self.file(span.file_id);
+        let line_number = file
+            .line_starts
+            .partition_point(|&start| start <= span.start)
+            .max(1)
+            - 1;
+        // Only the byte order mark comes before the start of the first line
+        Location {
+            file_name: &file.name,
+            line_number,
+            column: span.start.saturating_sub(file.line_starts[line_number]),
+        }
+    }
+}
+
+// Uses the same line breaks as the scanner. Like in the scanner, the first line starts after the byte order mark.
+fn line_starts(src: &str, policy: NewlinePolicy) -> Vec<usize> {
+    let s = src.as_bytes();
+    let bom_len = if src.starts_with('\u{feff}') {
+        '\u{feff}'.len_utf8()
+    } else {
+        0
+    };
+    let mut starts = vec![bom_len];
+    let mut i = bom_len;
+    while i < s.len() {
+        match policy.line_break_len(&s[i..]) {
+            0 => i += 1,
+            len => {
+                i += len;
+                starts.push(i);
+            }
+        }
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_tagged_with_file_id() {
+        let mut map = SourceMap::new();
+        let main = map.add_file("main.lox", "print 1;");
+        let lib = map.add_file("lib.lox", "print 2;\n\r  fun f() {}");
+
+        let main_tokens: Vec<_> = map.scanner(main).map(|r| r.unwrap()).collect();
+        let lib_tokens: Vec<_> = map.scanner(lib).map(|r| r.unwrap()).collect();
+        assert!(main_tokens.iter().all(|t| t.file_id == main));
+        assert!(lib_tokens.iter().all(|t| t.file_id == lib));
+
+        // Same offsets in different files are different spans.
+        assert_eq!(main_tokens[0].start_index_within_input, 0);
+        assert_eq!(lib_tokens[0].start_index_within_input, 0);
+        assert_ne!(main_tokens[0].span(), lib_tokens[0].span());
+
+        // Input scanned without the map doesn't belong to any of its files.
+        let plain = Scanner::new("print 1;").next().unwrap().unwrap();
+        assert_ne!(plain.span(), main_tokens[0].span());
+        assert_eq!(map.files().next().unwrap().0, main);
+
+        assert_eq!(
+            map.lookup(lib_tokens[3].span()),
+            Location {
+                file_name: "lib.lox",
+                line_number: 1,
+                column: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn errors_can_be_looked_up() {
+        let mut map = SourceMap::new();
+        map.add_file("main.lox", "print 1;");
+        let lib = map.add_file("lib.lox", "var x;\r\n  @");
+
+        let err = map.scanner(lib).find_map(Result::err).unwrap();
+        let err = err.downcast_ref::<UnexpectedSymbolError>().unwrap();
+        assert_eq!(err.file_id, lib);
+        assert_eq!(
+            map.lookup(err.span()),
+            Location {
+                file_name: "lib.lox",
+                line_number: 1,
+                column: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn lookup_agrees_with_the_scanner() {
+        // Columns on the first line don't count the byte order mark
+        let mut map = SourceMap::new();
+        let id = map.add_file("bom.lox", "\u{feff}var x;\n@");
+        let tokens: Vec<_> = map.scanner(id).collect();
+        let x = tokens[1].as_ref().unwrap();
+        assert_eq!(map.lookup(x.span()).column, x.start_index_within_line);
+        assert_eq!(map.lookup(x.span()).column, 4);
+
+        let err = tokens[3].as_ref().unwrap_err();
+        let err = err.downcast_ref::<UnexpectedSymbolError>().unwrap();
+        assert_eq!(map.lookup(err.span()).line_number, 1);
+
+        // A lone "\r" isn't a line break with `NewlinePolicy::Unix`
+        let mut map = SourceMap::new().newline_policy(NewlinePolicy::Unix);
+        let id = map.add_file("unix.lox", "a\rb\nc");
+        for token in map.scanner(id).map(Result::unwrap) {
+            let location = map.lookup(token.span());
+            assert_eq!(location.line_number, token.line_number);
+            assert_eq!(location.column, token.start_index_within_line);
+        }
+    }
+
+    #[test]
+    fn string_and_line_ending_errors_have_spans() {
+        let mut map = SourceMap::new();
+        let id = map.add_file("main.lox", "x = r\"abc\r\ny;\n");
+
+        let errors: Vec<_> = map
+            .scanner(id)
+            .report_mixed_line_endings(true)
+            .filter_map(Result::err)
+            .collect();
+        let unterminated = errors[0].downcast_ref::<UnterminatedStringError>();
+        let span = unterminated.unwrap().span();
+        assert_eq!((span.file_id, span.start, span.end), (id, 4, 9));
+        assert_eq!(map.lookup(span).column, 4);
+
+        let mixed = errors[1].downcast_ref::<MixedLineEndingsError>();
+        let span = mixed.unwrap().span();
+        assert_eq!((span.start, span.end), (13, 14));
+        assert_eq!(map.lookup(span).line_number, 1);
+    }
+
+    #[test]
+    fn load_file_reads_from_disk() {
+        // Unique per process, so that concurrent test runs don't share the file
+        let path =
+            std::env::temp_dir().join(format!("lox_source_map_test_{}.lox", std::process::id()));
+        fs::write(&path, "var x = 1;").unwrap();
+
+        let mut map = SourceMap::new();
+        let id = map.load_file(&path).unwrap();
+        assert_eq!(map.file(id).src(), "var x = 1;");
+        assert_eq!(map.file(id).name(), path.display().to_string());
+        assert_eq!(map.files().count(), 1);
+
+        fs::remove_file(&path).unwrap();
+        assert!(map.load_file(&path).is_err());
+    }
+}
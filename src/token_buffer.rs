@@ -0,0 +1,151 @@
+use std::ops::Range;
+
+use crate::lox_language_scanner::{Scanner, TokenKind};
+
+// Rough density of Lox source code: a token every few bytes once whitespace and comments are counted.
+// Used to pre-size the columns so that scanning a big file doesn't reallocate them over and over.
+const ESTIMATED_BYTES_PER_TOKEN: usize = 4;
+
+// Scanned tokens stored column-wise (struct of arrays).
+// Parsers mostly look at the kinds and only occasionally at positions or text,
+// so keeping every column in its own vector makes the hot loops cache friendly.
+pub struct TokenBuffer<'a> {
+    src: &'a str,
+    kinds: Vec<TokenKind>,
+    starts: Vec<usize>,
+    ends: Vec<usize>,
+    // Line number of every token. Only filled by `from_source_with_lines`.
+    lines: Option<Vec<usize>>,
+    errors: Vec<anyhow::Error>,
+}
+
+impl<'a> TokenBuffer<'a> {
+    pub fn from_source(src: &'a str) -> Self {
+        Self::scan(src, false)
+    }
+
+    pub fn from_source_with_lines(src: &'a str) -> Self {
+        Self::scan(src, true)
+    }
+
+    fn scan(src: &'a str, with_lines: bool) -> Self {
+        let capacity = src.len() / ESTIMATED_BYTES_PER_TOKEN + 1;
+        let mut buffer = Self {
+            src,
+            kinds: Vec::with_capacity(capacity),
+            starts: Vec::with_capacity(capacity),
+            ends: Vec::with_capacity(capacity),
+            lines: with_lines.then(|| Vec::with_capacity(capacity)),
+            errors: Vec::new(),
+        };
+
+        // Scanning errors don't stop the scanner, so we collect them and keep going.
+        for result in Scanner::new(src) {
+            match result {
+                Ok(token) => {
+                    buffer.kinds.push(token.token_type.kind());
+                    buffer.starts.push(token.start_index_within_input);
+                    buffer.ends.push(token.end_index_within_input);
+                    if let Some(lines) = &mut buffer.lines {
+                        lines.push(token.line_number);
+                    }
+                }
+                Err(err) => buffer.errors.push(err),
+            }
+        }
+
+        buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    pub fn kinds(&self) -> &[TokenKind] {
+        &self.kinds
+    }
+
+    pub fn kind(&self, i: usize) -> TokenKind {
+        self.kinds[i]
+    }
+
+    // Byte range of the token within the source. Like in `Token`, strings don't include the quotation marks.
+    pub fn span(&self, i: usize) -> Range<usize> {
+        self.starts[i]..self.ends[i]
+    }
+
+    pub fn text(&self, i: usize) -> &'a str {
+        &self.src[self.span(i)]
+    }
+
+    // Returns `None` if the buffer was created without the line table.
+    pub fn line(&self, i: usize) -> Option<usize> {
+        self.lines.as_ref().map(|lines| lines[i])
+    }
+
+    pub fn errors(&self) -> &[anyhow::Error] {
+        &self.errors
+    }
+
+    // Index of the token that covers the given byte offset, if any.
+    // A token covers the offsets `start..end`, so whitespace, comments and the quotation marks of strings
+    // don't belong to any token.
+    pub fn token_at_offset(&self, offset: usize) -> Option<usize> {
+        let candidate = self.starts.partition_point(|&start| start <= offset);
+        if candidate == 0 {
+            return None;
+        }
+
+        let i = candidate - 1;
+        (offset < self.ends[i]).then_some(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_tokens_column_wise() {
+        let buffer = TokenBuffer::from_source("var x = \"hi\";");
+        assert_eq!(buffer.len(), 5);
+
+        use TokenKind::*;
+        assert_eq!(buffer.kinds(), &[VAR, IDENTIFIER, EQUAL, STRING, SEMICOLON]);
+        assert_eq!(buffer.span(1), 4..5);
+        assert_eq!(buffer.text(1), "x");
+        assert_eq!(buffer.text(3), "hi");
+        assert_eq!(buffer.line(0), None);
+    }
+
+    #[test]
+    fn line_table_is_optional() {
+        let buffer = TokenBuffer::from_source_with_lines("1\n2\n\n3");
+        assert_eq!(buffer.line(0), Some(0));
+        assert_eq!(buffer.line(1), Some(1));
+        assert_eq!(buffer.line(2), Some(3));
+    }
+
+    #[test]
+    fn errors_are_collected_and_scanning_continues() {
+        let buffer = TokenBuffer::from_source("1 @ 2");
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.errors().len(), 1);
+    }
+
+    #[test]
+    fn token_at_offset_finds_covering_token() {
+        let buffer = TokenBuffer::from_source("print  foo;");
+        assert_eq!(buffer.token_at_offset(0), Some(0));
+        assert_eq!(buffer.token_at_offset(4), Some(0));
+        assert_eq!(buffer.token_at_offset(5), None);
+        assert_eq!(buffer.token_at_offset(7), Some(1));
+        assert_eq!(buffer.token_at_offset(9), Some(1));
+        assert_eq!(buffer.token_at_offset(10), Some(2));
+        assert_eq!(buffer.token_at_offset(11), None);
+    }
+}
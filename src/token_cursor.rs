@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::lox_language_scanner::{Scanner, Token};
+
+const DEFAULT_CAPACITY: usize = 64;
+
+// Position in the token stream that the cursor can be rewound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
+#[derive(Debug, Error)]
+#[error("Can't rewind to token {position}: it was already dropped from the buffer")]
+pub struct CheckpointEvictedError {
+    pub position: usize,
+}
+
+// Cursor over the scanner output with multi-token lookahead and backtracking.
+// Works with any source of tokens, like a `Scanner` with an extension or a filtered token stream.
+// Tokens are scanned lazily, only when the parser looks at them, and kept in a ring buffer.
+// When the buffer is full, the oldest token behind the cursor is dropped, which limits how far back a checkpoint
+// can be rewound to. Peeking further ahead than the capacity grows the buffer instead.
+pub struct TokenCursor<'a, I = Scanner<'a>> {
+    scanner: I,
+    buffer: VecDeque<Result<Token<'a>>>,
+    // Index of the first buffered token within the whole token stream.
+    buffer_start: usize,
+    position: usize,
+    capacity: usize,
+}
+
+impl<'a, I: Iterator<Item = Result<Token<'a>>>> TokenCursor<'a, I> {
+    pub fn new(scanner: I) -> Self {
+        Self::with_capacity(scanner, DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(scanner: I, capacity: usize) -> Self {
+        assert!(capacity > 0, "buffer capacity must not be zero");
+        Self {
+            scanner,
+            buffer: VecDeque::with_capacity(capacity),
+            buffer_start: 0,
+            position: 0,
+            capacity,
+        }
+    }
+
+    // Index of the next token within the whole token stream.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn peek(&mut self) -> Option<&Result<Token<'a>>> {
+        self.peek_nth(0)
+    }
+
+    // Returns the k-th token after the cursor without consuming anything. `peek_nth(0)` is the next token.
+    pub fn peek_nth(&mut self, k: usize) -> Option<&Result<Token<'a>>> {
+        let index = self.position + k;
+        if !self.fill(index) {
+            return None;
+        }
+        self.buffer.get(index - self.buffer_start)
+    }
+
+    pub fn next_token(&mut self) -> Option<&Result<Token<'a>>> {
+        let index = self.position;
+        if !self.fill(index) {
+            return None;
+        }
+        self.position += 1;
+        self.buffer.get(index - self.buffer_start)
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.position)
+    }
+
+    pub fn rewind(&mut self, checkpoint: Checkpoint) -> Result<(), CheckpointEvictedError> {
+        if checkpoint.0 < self.buffer_start {
+            return Err(CheckpointEvictedError {
+                position: checkpoint.0,
+            });
+        }
+        self.position = checkpoint.0;
+        Ok(())
+    }
+
+    // Scans tokens until the token with the given index is buffered. Returns false if the input ends before it.
+    fn fill(&mut self, index: usize) -> bool {
+        while self.buffer_start + self.buffer.len() <= index {
+            let Some(item) = self.scanner.next() else {
+                return false;
+            };
+
+            // Only tokens behind the cursor are dropped, tokens ahead of it may still be needed
+            while self.buffer.len() >= self.capacity && self.buffer_start < self.position {
+                self.buffer.pop_front();
+                self.buffer_start += 1;
+            }
+            self.buffer.push_back(item);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox_language_scanner::TokenType;
+
+    fn token_type<'a>(item: Option<&Result<Token<'a>>>) -> TokenType<'a> {
+        item.unwrap().as_ref().unwrap().token_type.clone()
+    }
+
+    #[test]
+    fn peek_nth_looks_ahead_lazily() {
+        let mut cursor = TokenCursor::new(Scanner::new("(a, b) { return a; }"));
+        assert_eq!(token_type(cursor.peek_nth(2)), TokenType::COMMA);
+        assert_eq!(cursor.buffer.len(), 3);
+
+        assert_eq!(token_type(cursor.peek()), TokenType::LEFT_PAREN);
+        assert_eq!(token_type(cursor.next_token()), TokenType::LEFT_PAREN);
+        assert_eq!(token_type(cursor.peek()), TokenType::IDENTIFIER("a"));
+        assert!(cursor.peek_nth(100).is_none());
+    }
+
+    #[test]
+    fn rewind_returns_to_checkpoint() {
+        let mut cursor = TokenCursor::new(Scanner::new("(a, b) { return a; }"));
+        let checkpoint = cursor.checkpoint();
+
+        // Try to parse a parameter list, then backtrack.
+        while let Some(item) = cursor.next_token() {
+            if item.as_ref().unwrap().token_type == TokenType::RIGHT_PAREN {
+                break;
+            }
+        }
+        assert_eq!(token_type(cursor.peek()), TokenType::LEFT_BRACE);
+
+        cursor.rewind(checkpoint).unwrap();
+        assert_eq!(cursor.position(), 0);
+        assert_eq!(token_type(cursor.next_token()), TokenType::LEFT_PAREN);
+    }
+
+    #[test]
+    fn old_checkpoints_are_evicted() {
+        let mut cursor = TokenCursor::with_capacity(Scanner::new("1 2 3 4 5"), 2);
+        let checkpoint = cursor.checkpoint();
+        for _ in 0..4 {
+            cursor.next_token();
+        }
+        assert_eq!(token_type(cursor.next_token()), TokenType::NUMBER(5.0));
+        assert!(cursor.next_token().is_none());
+
+        let err = cursor.rewind(checkpoint).unwrap_err();
+        assert_eq!(err.position, 0);
+        cursor.rewind(Checkpoint(3)).unwrap();
+        assert_eq!(token_type(cursor.next_token()), TokenType::NUMBER(4.0));
+    }
+
+    #[test]
+    fn lookahead_beyond_capacity_grows_the_buffer() {
+        let mut cursor = TokenCursor::with_capacity(Scanner::new("1 2 3 4 5"), 2);
+        assert_eq!(token_type(cursor.peek_nth(3)), TokenType::NUMBER(4.0));
+        assert_eq!(cursor.buffer.len(), 4);
+        assert_eq!(token_type(cursor.next_token()), TokenType::NUMBER(1.0));
+
+        // Moving on shrinks the buffer back to its capacity
+        cursor.next_token();
+        cursor.next_token();
+        assert_eq!(token_type(cursor.peek_nth(1)), TokenType::NUMBER(5.0));
+        assert_eq!(cursor.buffer.len(), 2);
+        assert!(cursor.rewind(Checkpoint(2)).is_err());
+    }
+
+    #[test]
+    fn works_with_any_token_iterator() {
+        let tokens = Scanner::new("a // comment\nb")
+            .trivia(true)
+            .filter(|item| !matches!(item, Ok(token) if token.token_type.kind().is_trivia()));
+        let mut cursor = TokenCursor::new(tokens);
+        assert_eq!(token_type(cursor.peek_nth(1)), TokenType::IDENTIFIER("b"));
+    }
+}
@@ -0,0 +1,337 @@
+use std::fmt::Write;
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::lox_language_scanner::{Scanner, Token, TokenKind};
+use crate::visual_column::{DEFAULT_TAB_WIDTH, visual_column};
+
+// Scanned source file, the input of a diff.
+pub struct TokenFile<'a> {
+    pub path: &'a str,
+    pub src: &'a str,
+    pub tokens: Vec<Token<'a>>,
+}
+
+impl<'a> TokenFile<'a> {
+    // Scans without trivia, so whitespace and comments don't take part in the diff.
+    // A diff of a file the scanner can't read would be misleading, so scanning errors are returned instead.
+    pub fn scan(path: &'a str, src: &'a str) -> Result<Self, Vec<anyhow::Error>> {
+        let (tokens, errors): (Vec<_>, Vec<_>) = Scanner::new(src).partition(Result::is_ok);
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(Result::unwrap_err).collect());
+        }
+        Ok(Self {
+            path,
+            src,
+            tokens: tokens.into_iter().map(Result::unwrap).collect(),
+        })
+    }
+
+    // Tokens are equal if they have the same kind and the same source text.
+    fn key(&self, index: usize) -> (TokenKind, &'a str) {
+        let token = &self.tokens[index];
+        (token.token_type.kind(), token.lexeme(self.src))
+    }
+
+    // 1-based line and column of the token at `index`, or of the end of the last token if `index` is past it.
+    // The column is the one of the lexeme, so it points at the quotation mark of a string.
+    fn position(&self, index: usize) -> (usize, usize) {
+        let (token, byte) = match (self.tokens.get(index), self.tokens.last()) {
+            (Some(token), _) => (token, token.lexeme_range(self.src).start),
+            (None, Some(last)) => (last, last.lexeme_range(self.src).end),
+            (None, None) => return (1, 1),
+        };
+        let line = &self.src[token.line_start_index..];
+        let column = visual_column(line, byte - token.line_start_index, DEFAULT_TAB_WIDTH);
+        (token.line_number + 1, column + 1)
+    }
+}
+
+// Tokens `old` of the old file were replaced with the tokens `new` of the new file. One of the ranges can be empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Remove,
+    Insert,
+}
+
+// Smallest set of token changes that turns `old` into `new`. Formatting and comments are ignored.
+pub fn diff_tokens(old: &TokenFile, new: &TokenFile) -> Vec<Hunk> {
+    let old_keys: Vec<_> = (0..old.tokens.len()).map(|i| old.key(i)).collect();
+    let new_keys: Vec<_> = (0..new.tokens.len()).map(|i| new.key(i)).collect();
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut hunk: Option<Hunk> = None;
+    for edit in edit_script(&old_keys, &new_keys) {
+        if edit == Edit::Keep {
+            hunks.extend(hunk.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let hunk = hunk.get_or_insert(Hunk {
+            old: i..i,
+            new: j..j,
+        });
+        if edit == Edit::Remove {
+            i += 1;
+            hunk.old.end = i;
+        } else {
+            j += 1;
+            hunk.new.end = j;
+        }
+    }
+    hunks.extend(hunk);
+    hunks
+}
+
+// Myers' O((N + M) D) shortest edit script, where D is the number of removed and inserted items, in the linear
+// space variant: the middle of the path is found by searching from both ends, then both halves are solved the same
+// way. Common prefixes and suffixes are skipped first, which is where most of the tokens of a reviewed file are.
+fn edit_script<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let mut script = Vec::with_capacity(a.len().max(b.len()));
+    append_edit_script(a, b, &mut script);
+    script
+}
+
+fn append_edit_script<T: PartialEq>(a: &[T], b: &[T], script: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    script.extend(std::iter::repeat_n(Edit::Keep, prefix));
+    if a_mid.is_empty() || b_mid.is_empty() {
+        script.extend(std::iter::repeat_n(Edit::Remove, a_mid.len()));
+        script.extend(std::iter::repeat_n(Edit::Insert, b_mid.len()));
+    } else {
+        // Both halves have fewer differences than the whole, since the ends of `a_mid` and `b_mid` differ
+        let (x, y, u) = middle_snake(a_mid, b_mid);
+        append_edit_script(&a_mid[..x], &b_mid[..y], script);
+        script.extend(std::iter::repeat_n(Edit::Keep, u - x));
+        append_edit_script(&a_mid[u..], &b_mid[y + u - x..], script);
+    }
+    script.extend(std::iter::repeat_n(Edit::Keep, suffix));
+}
+
+// Diagonal run of equal items in the middle of a shortest edit path, as its start in `a` and `b` and its end in `a`.
+// Searches forward from the start and backward from the end at the same time, until the two searches overlap.
+fn middle_snake<T: PartialEq>(a: &[T], b: &[T]) -> (usize, usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m + 1) / 2;
+    // Diagonal k = x - y of the forward search is diagonal `delta - k` of the backward search
+    let delta = n - m;
+    // `forward[k]` is the furthest x reached on diagonal k, `backward[k]` the furthest distance from the end.
+    // Indices are shifted, since k can be negative.
+    let index = |k: isize| (k + max + 1) as usize;
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                forward[index(k + 1)]
+            } else {
+                forward[index(k - 1)] + 1
+            };
+            let start = x;
+            while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+                x += 1;
+            }
+            forward[index(k)] = x;
+            // With an odd delta the paths meet after a forward step
+            let back_k = delta - k;
+            if delta % 2 != 0
+                && (-(d - 1)..=d - 1).contains(&back_k)
+                && x + backward[index(back_k)] >= n
+            {
+                return (start as usize, (start - k) as usize, x as usize);
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[index(k - 1)] < backward[index(k + 1)]) {
+                backward[index(k + 1)]
+            } else {
+                backward[index(k - 1)] + 1
+            };
+            let start = x;
+            while x < n && x - k < m && a[(n - 1 - x) as usize] == b[(m - 1 - (x - k)) as usize] {
+                x += 1;
+            }
+            backward[index(k)] = x;
+            // With an even delta the paths meet after a backward step
+            let forward_k = delta - k;
+            if delta % 2 == 0 && (-d..=d).contains(&forward_k) && forward[index(forward_k)] + x >= n
+            {
+                let (end_x, end_y) = (n - start, m - (start - k));
+                let (x, y) = (n - x, m - (x - k));
+                debug_assert_eq!(end_x - x, end_y - y);
+                return (x as usize, y as usize, end_x as usize);
+            }
+        }
+    }
+    unreachable!("the searches always meet")
+}
+
+// Prints every hunk with its position in both files, then the removed and the inserted tokens:
+//
+// old.lox:3:7 new.lox:3:7
+// - x
+// + y
+pub fn render_diff(old: &TokenFile, new: &TokenFile, hunks: &[Hunk]) -> String {
+    let mut out = String::new();
+    for hunk in hunks {
+        let (old_line, old_column) = old.position(hunk.old.start);
+        let (new_line, new_column) = new.position(hunk.new.start);
+        writeln!(
+            out,
+            "{}:{old_line}:{old_column} {}:{new_line}:{new_column}",
+            old.path, new.path
+        )
+        .unwrap();
+        for (sign, file, range) in [('-', old, &hunk.old), ('+', new, &hunk.new)] {
+            if range.is_empty() {
+                continue;
+            }
+            let lexemes: Vec<_> = file.tokens[range.clone()]
+                .iter()
+                .map(|token| token.lexeme(file.src))
+                .collect();
+            writeln!(out, "{sign} {}", lexemes.join(" ")).unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> String {
+        let old = TokenFile::scan("old.lox", old).unwrap();
+        let new = TokenFile::scan("new.lox", new).unwrap();
+        render_diff(&old, &new, &diff_tokens(&old, &new))
+    }
+
+    #[test]
+    fn ignores_formatting_and_comments() {
+        assert_eq!(
+            diff(
+                "var x = 1;\nprint x;",
+                "// setup\nvar x=1;   print\n  x; // done"
+            ),
+            ""
+        );
+    }
+
+    #[test]
+    fn reports_changes_with_positions_in_both_files() {
+        let old = "var x = 1;\nprint x;\n";
+        let new = "// header\nvar x = 2;\nprint x + 1;\n";
+        assert_eq!(
+            diff(old, new),
+            "old.lox:1:9 new.lox:2:9\n- 1\n+ 2\nold.lox:2:8 new.lox:3:9\n+ + 1\n"
+        );
+        assert_eq!(diff("a; b;", "a;"), "old.lox:1:4 new.lox:1:3\n- b ;\n");
+    }
+
+    #[test]
+    fn string_and_number_text_is_compared() {
+        assert_eq!(
+            diff("print \"a\" + 1.0;", "print \"b\" + 1;"),
+            "old.lox:1:7 new.lox:1:7\n- \"a\"\n+ \"b\"\nold.lox:1:13 new.lox:1:13\n- 1.0\n+ 1\n"
+        );
+    }
+
+    // The number of removed and inserted items is minimal, checked against the length of the longest common
+    // subsequence for all short sequences over two letters.
+    #[test]
+    fn edit_script_is_minimal() {
+        let sequences: Vec<Vec<u8>> = (0..6)
+            .flat_map(|len| {
+                (0..1 << len).map(move |bits| (0..len).map(|i| (bits >> i) as u8 & 1).collect())
+            })
+            .collect();
+        for a in &sequences {
+            for b in &sequences {
+                let (end, keeps) = apply(a, b);
+                assert_eq!(end, (a.len(), b.len()));
+                assert_eq!(keeps, lcs_len(a, b), "{a:?} {b:?}");
+            }
+        }
+    }
+
+    // Large inputs with many differences. Keeping the search state of every round took O(D (N + M)) memory,
+    // which was more than a gigabyte for the unrelated sequences.
+    #[test]
+    fn edit_script_of_large_inputs() {
+        let mut seed = 1u32;
+        let mut random = |len: usize, letters: u32| -> Vec<u32> {
+            (0..len)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    seed % letters
+                })
+                .collect()
+        };
+        let unrelated: Vec<u32> = (0..4000).map(|i| i + 4000).collect();
+        let old = random(4000, 4);
+        let new = random(3000, 4);
+
+        assert_eq!(
+            edit_script(&old, &unrelated),
+            [vec![Edit::Remove; 4000], vec![Edit::Insert; 4000]].concat()
+        );
+        let (end, keeps) = apply(&old, &new);
+        assert_eq!(end, (old.len(), new.len()));
+        assert_eq!(keeps, lcs_len(&old, &new));
+    }
+
+    // Walks the edit script, checking that kept items are equal. Returns how far it got in both sequences
+    // and the number of kept items.
+    fn apply<T: PartialEq + std::fmt::Debug>(a: &[T], b: &[T]) -> ((usize, usize), usize) {
+        let (mut i, mut j, mut keeps) = (0, 0, 0);
+        for edit in edit_script(a, b) {
+            match edit {
+                Edit::Keep => {
+                    assert_eq!(a[i], b[j], "{a:?} {b:?}");
+                    (i, j, keeps) = (i + 1, j + 1, keeps + 1);
+                }
+                Edit::Remove => i += 1,
+                Edit::Insert => j += 1,
+            }
+        }
+        ((i, j), keeps)
+    }
+
+    // Two rows of the usual dynamic programming table.
+    fn lcs_len<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+        let mut previous = vec![0; b.len() + 1];
+        let mut row = vec![0; b.len() + 1];
+        for x in a {
+            for (j, y) in b.iter().enumerate() {
+                row[j + 1] = if x == y {
+                    previous[j] + 1
+                } else {
+                    previous[j + 1].max(row[j])
+                };
+            }
+            std::mem::swap(&mut previous, &mut row);
+        }
+        previous[b.len()]
+    }
+}
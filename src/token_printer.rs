@@ -0,0 +1,149 @@
+use crate::lox_language_scanner::{Token, TokenType};
+
+// Rebuilds canonical Lox source code from tokens, for example to emit code that was generated or transformed
+// as a token stream. Tokens are separated by a single space, so the result scans back to the same tokens.
+// Trivia tokens are dropped. Custom tokens are written as they were scanned: the result only scans back to them
+// with the same `ScannerExtension`, a scanner without it reports them as unexpected symbols.
+pub fn tokens_to_source<'a>(tokens: impl IntoIterator<Item = &'a Token<'a>>) -> String {
+    let mut out = String::new();
+    let tokens = tokens
+        .into_iter()
+        .filter(|token| !token.token_type.kind().is_trivia());
+    for (i, token) in tokens.enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write_token(&mut out, &token.token_type);
+    }
+    out
+}
+
+// Appends the canonical source text of a single token.
+// Numbers are printed in their shortest form, so `1.50` becomes `1.5` and `2.0` becomes `2`.
+// A literal too large for `f64` is infinite, it is printed as the shortest literal that is infinite too.
+pub fn write_token(out: &mut String, token_type: &TokenType) {
+    match token_type {
+        TokenType::IDENTIFIER(name) => out.push_str(name),
+        TokenType::STRING(content) => {
+            out.push('"');
+            out.push_str(content);
+            out.push('"');
+        }
+        TokenType::STRING_START(content) => {
+            out.push('"');
+            out.push_str(content);
+            out.push_str("${");
+        }
+        TokenType::STRING_PART(content) => {
+            out.push('}');
+            out.push_str(content);
+            out.push_str("${");
+        }
+        TokenType::STRING_END(content) => {
+            out.push('}');
+            out.push_str(content);
+            out.push('"');
+        }
+        TokenType::RAW_STRING(content) => {
+            // As few "#" as possible, so that the content doesn't close the string early
+            let hashes = (0..)
+                .map(|n| "#".repeat(n))
+                .find(|hashes| !content.contains(&format!("\"{hashes}")))
+                .unwrap();
+            out.push('r');
+            out.push_str(&hashes);
+            out.push('"');
+            out.push_str(content);
+            out.push('"');
+            out.push_str(&hashes);
+        }
+        TokenType::NUMBER(value) if value.is_infinite() => {
+            out.push('2');
+            out.push_str(&"0".repeat(f64::MAX_10_EXP as usize));
+        }
+        TokenType::NUMBER(value) => out.push_str(&value.to_string()),
+        TokenType::WHITESPACE => out.push(' '),
+        TokenType::NEWLINE => out.push('\n'),
+        TokenType::COMMENT(text) | TokenType::SHEBANG(text) | TokenType::CUSTOM(_, text) => {
+            out.push_str(text)
+        }
+        other => out.push_str(
+            other
+                .kind()
+                .fixed_lexeme()
+                .expect("tokens without a payload have a fixed lexeme"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox_language_scanner::{Scanner, ScannerExtension};
+
+    #[test]
+    fn rebuilds_canonical_source() {
+        let src = "var  x=1.50;// comment\nprint \"hi\"  +x ;";
+        let tokens: Vec<_> = Scanner::new(src).map(|r| r.unwrap()).collect();
+        assert_eq!(
+            tokens_to_source(&tokens),
+            "var x = 1.5 ; print \"hi\" + x ;"
+        );
+    }
+
+    #[test]
+    fn canonical_source_scans_back_to_same_tokens() {
+        assert_round_trip("if(a>=b and!c){return 1.+2;}else{fun f(){}}");
+    }
+
+    #[test]
+    fn interpolated_and_raw_strings_scan_back_to_same_tokens() {
+        assert_round_trip("print \"a${ {x}.y }b${\"c${d}\"}e\" + r#\"say \"hi\"\"# + r\"\\n\";");
+    }
+
+    #[test]
+    fn custom_tokens_scan_back_only_with_the_same_extension() {
+        struct Annotations;
+        impl ScannerExtension for Annotations {
+            fn scan(&mut self, rest: &str) -> Option<(u16, usize)> {
+                let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '@')?;
+                (rest.starts_with('@') && len > 1).then_some((1, len))
+            }
+        }
+
+        let src = "@inline fun f(){}";
+        let tokens: Vec<_> = Scanner::new(src)
+            .extension(Annotations)
+            .map(|r| r.unwrap())
+            .collect();
+        let rebuilt = tokens_to_source(&tokens);
+        assert_eq!(rebuilt, "@inline fun f ( ) { }");
+
+        let rescanned: Vec<_> = Scanner::new(&rebuilt)
+            .extension(Annotations)
+            .map(|r| r.unwrap().token_type)
+            .collect();
+        let original: Vec<_> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(rescanned, original);
+        assert!(Scanner::new(&rebuilt).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn numbers_too_large_for_f64_scan_back_to_same_tokens() {
+        let src = "1".repeat(400) + " + 0.5";
+        assert_round_trip(&src);
+        let tokens: Vec<_> = Scanner::new(&src).map(|r| r.unwrap()).collect();
+        assert!(!tokens_to_source(&tokens).contains("inf"));
+    }
+
+    fn assert_round_trip(src: &str) {
+        let tokens: Vec<_> = Scanner::new(src).map(|r| r.unwrap()).collect();
+        let rebuilt = tokens_to_source(&tokens);
+
+        let rescanned: Vec<_> = Scanner::new(&rebuilt)
+            .map(|r| r.unwrap().token_type)
+            .collect();
+        let original: Vec<_> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(rescanned, original);
+    }
+}
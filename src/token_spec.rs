@@ -0,0 +1,83 @@
+// Token specification. `build.rs` includes this file and generates from it the `TokenType` and `TokenKind` enums,
+// `TokenKind::ALL`, `TokenType::kind`, `TokenKind::fixed_lexeme`, the `Display` and `FromStr` impls of `TokenKind`
+// and the re2c rules of the fixed tokens. Adding an operator or a keyword is a one-line change here.
+
+// Tokens that always look the same: (name, lexeme, cargo feature). Each of them gets a re2c rule,
+// except `HAND_WRITTEN_RULES`. Tokens with a feature only exist when the feature is enabled.
+// When two rules match the same text, the first one wins, so keywords are listed before identifiers are matched.
+const FIXED_TOKENS: &[(&str, &str, &str)] = &[
+    // Single-character tokens.
+    ("LEFT_PAREN", "(", ""),
+    ("RIGHT_PAREN", ")", ""),
+    ("LEFT_BRACE", "{", ""),
+    ("RIGHT_BRACE", "}", ""),
+    ("COMMA", ",", ""),
+    ("DOT", ".", ""),
+    ("MINUS", "-", ""),
+    ("PLUS", "+", ""),
+    ("SEMICOLON", ";", ""),
+    ("SLASH", "/", ""),
+    ("STAR", "*", ""),
+    // One or two character tokens.
+    ("BANG", "!", ""),
+    ("BANG_EQUAL", "!=", ""),
+    ("EQUAL", "=", ""),
+    ("EQUAL_EQUAL", "==", ""),
+    ("GREATER", ">", ""),
+    ("GREATER_EQUAL", ">=", ""),
+    ("LESS", "<", ""),
+    ("LESS_EQUAL", "<=", ""),
+    // Extended operators, common in Lox forks.
+    ("PERCENT", "%", "extended-operators"),
+    ("PLUS_EQUAL", "+=", "extended-operators"),
+    ("MINUS_EQUAL", "-=", "extended-operators"),
+    ("STAR_EQUAL", "*=", "extended-operators"),
+    ("SLASH_EQUAL", "/=", "extended-operators"),
+    ("QUESTION", "?", "extended-operators"),
+    ("COLON", ":", "extended-operators"),
+    ("STAR_STAR", "**", "extended-operators"),
+    ("PLUS_PLUS", "++", "extended-operators"),
+    ("MINUS_MINUS", "--", "extended-operators"),
+    // Keywords.
+    ("AND", "and", ""),
+    ("CLASS", "class", ""),
+    ("ELSE", "else", ""),
+    ("FALSE", "false", ""),
+    ("FUN", "fun", ""),
+    ("FOR", "for", ""),
+    ("IF", "if", ""),
+    ("NIL", "nil", ""),
+    ("OR", "or", ""),
+    ("PRINT", "print", ""),
+    ("RETURN", "return", ""),
+    ("SUPER", "super", ""),
+    ("THIS", "this", ""),
+    ("TRUE", "true", ""),
+    ("VAR", "var", ""),
+    ("WHILE", "while", ""),
+];
+
+// Fixed tokens whose re2c rules are written by hand, because the scanner has to track them.
+// Braces find the end of an interpolation inside of a string.
+const HAND_WRITTEN_RULES: &[&str] = &["LEFT_BRACE", "RIGHT_BRACE"];
+
+// Tokens that carry a value: (name, payload types). Their re2c rules are written by hand in
+// `lox_language_scanner.re2c.rs`. An empty payload means a variant without fields.
+const VALUE_TOKENS: &[(&str, &str)] = &[
+    // Literals.
+    ("IDENTIFIER", "&'a str"),
+    ("STRING", "&'a str"),
+    // Interpolated string "a${x}b${y}c": STRING_START "a", tokens of x, STRING_PART "b", tokens of y, STRING_END "c".
+    ("STRING_START", "&'a str"),
+    ("STRING_PART", "&'a str"),
+    ("STRING_END", "&'a str"),
+    ("RAW_STRING", "&'a str"),
+    ("NUMBER", "f64"),
+    // Trivia. Only produced in trivia mode.
+    ("WHITESPACE", ""),
+    ("NEWLINE", ""),
+    ("COMMENT", "&'a str"),
+    ("SHEBANG", "&'a str"),
+    // Lexeme recognized by a `ScannerExtension`, with the kind the extension gave it.
+    ("CUSTOM", "u16, &'a str"),
+];
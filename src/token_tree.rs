@@ -0,0 +1,313 @@
+use thiserror::Error;
+
+use crate::lox_language_scanner::{Token, TokenKind};
+use crate::source_map::Span;
+
+// Token, or a group of tokens between matching delimiters. Tokens are referred to by their index
+// within the slice the trees were built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenTree {
+    Token(usize),
+    Group(Group),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub delimiter: Delimiter,
+    // Index of the opening delimiter.
+    pub open: usize,
+    // Index of the closing delimiter. `None` if the group isn't closed.
+    pub close: Option<usize>,
+    pub trees: Vec<TokenTree>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Paren,
+    Brace,
+}
+
+impl Delimiter {
+    fn opened_by(kind: TokenKind) -> Option<Delimiter> {
+        match kind {
+            TokenKind::LEFT_PAREN => Some(Delimiter::Paren),
+            TokenKind::LEFT_BRACE => Some(Delimiter::Brace),
+            _ => None,
+        }
+    }
+
+    fn closed_by(kind: TokenKind) -> Option<Delimiter> {
+        match kind {
+            TokenKind::RIGHT_PAREN => Some(Delimiter::Paren),
+            TokenKind::RIGHT_BRACE => Some(Delimiter::Brace),
+            _ => None,
+        }
+    }
+
+    pub fn open_char(self) -> char {
+        match self {
+            Delimiter::Paren => '(',
+            Delimiter::Brace => '{',
+        }
+    }
+
+    pub fn close_char(self) -> char {
+        match self {
+            Delimiter::Paren => ')',
+            Delimiter::Brace => '}',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum DelimiterError {
+    // The input ends before the group is closed.
+    #[error("unclosed `{}`", delimiter.open_char())]
+    Unclosed { delimiter: Delimiter, open: Span },
+    // Closing delimiter without any open group.
+    #[error("unexpected `{}`", delimiter.close_char())]
+    Unexpected { delimiter: Delimiter, close: Span },
+    // Closing delimiter that doesn't match the innermost open group.
+    #[error("mismatched delimiters: `{}` opened here, `{}` found here", open_delimiter.open_char(), close_delimiter.close_char())]
+    Mismatched {
+        open_delimiter: Delimiter,
+        open: Span,
+        close_delimiter: Delimiter,
+        close: Span,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenTrees {
+    pub trees: Vec<TokenTree>,
+    pub errors: Vec<DelimiterError>,
+    // For every token index, the index of the matching delimiter.
+    matching: Vec<Option<usize>>,
+}
+
+impl TokenTrees {
+    // Index of the delimiter that matches the delimiter at `index`, in both directions.
+    // `None` for other tokens and for delimiters without a match.
+    pub fn matching_delimiter(&self, index: usize) -> Option<usize> {
+        self.matching.get(index).copied().flatten()
+    }
+}
+
+// Nests the tokens between "(" and ")" and between "{" and "}" into groups.
+// Doesn't stop at errors, so it works on code that is being edited:
+// - a closing delimiter that matches an enclosing group closes it, and cuts short the groups opened inside of it,
+// - a closing delimiter that doesn't match any open group is reported and kept as a plain token,
+// - groups still open at the end of the input are reported and closed there.
+pub fn build_token_trees(tokens: &[Token]) -> TokenTrees {
+    let mut matching = vec![None; tokens.len()];
+    let mut errors = Vec::new();
+    let mut trees = Vec::new();
+    // Groups that aren't closed yet, innermost last.
+    let mut open: Vec<Group> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let kind = token.token_type.kind();
+        if let Some(delimiter) = Delimiter::opened_by(kind) {
+            open.push(Group {
+                delimiter,
+                open: i,
+                close: None,
+                trees: Vec::new(),
+            });
+            continue;
+        }
+        let Some(delimiter) = Delimiter::closed_by(kind) else {
+            innermost(&mut open, &mut trees).push(TokenTree::Token(i));
+            continue;
+        };
+
+        let Some(matching_group) = open.iter().rposition(|group| group.delimiter == delimiter)
+        else {
+            errors.push(match open.last() {
+                Some(group) => DelimiterError::Mismatched {
+                    open_delimiter: group.delimiter,
+                    open: tokens[group.open].span(),
+                    close_delimiter: delimiter,
+                    close: token.span(),
+                },
+                None => DelimiterError::Unexpected {
+                    delimiter,
+                    close: token.span(),
+                },
+            });
+            innermost(&mut open, &mut trees).push(TokenTree::Token(i));
+            continue;
+        };
+
+        // The innermost of the groups that are cut short faces the closing delimiter, the others are just unclosed.
+        let mut faces_delimiter = true;
+        while open.len() > matching_group + 1 {
+            let group = open.pop().unwrap();
+            errors.push(if faces_delimiter {
+                DelimiterError::Mismatched {
+                    open_delimiter: group.delimiter,
+                    open: tokens[group.open].span(),
+                    close_delimiter: delimiter,
+                    close: token.span(),
+                }
+            } else {
+                DelimiterError::Unclosed {
+                    delimiter: group.delimiter,
+                    open: tokens[group.open].span(),
+                }
+            });
+            faces_delimiter = false;
+            innermost(&mut open, &mut trees).push(TokenTree::Group(group));
+        }
+
+        let mut group = open.pop().unwrap();
+        group.close = Some(i);
+        matching[group.open] = Some(i);
+        matching[i] = Some(group.open);
+        innermost(&mut open, &mut trees).push(TokenTree::Group(group));
+    }
+
+    errors.extend(open.iter().map(|group| DelimiterError::Unclosed {
+        delimiter: group.delimiter,
+        open: tokens[group.open].span(),
+    }));
+    while let Some(group) = open.pop() {
+        innermost(&mut open, &mut trees).push(TokenTree::Group(group));
+    }
+
+    TokenTrees {
+        trees,
+        errors,
+        matching,
+    }
+}
+
+// Trees of the innermost open group, or the top level trees.
+fn innermost<'t>(open: &'t mut [Group], trees: &'t mut Vec<TokenTree>) -> &'t mut Vec<TokenTree> {
+    match open.last_mut() {
+        Some(group) => &mut group.trees,
+        None => trees,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox_language_scanner::Scanner;
+
+    fn build(src: &str) -> TokenTrees {
+        let tokens: Vec<_> = Scanner::new(src).map(|r| r.unwrap()).collect();
+        build_token_trees(&tokens)
+    }
+
+    #[test]
+    fn nests_groups() {
+        // fun f ( a ) { print ( a ) ; }
+        // 0   1 2 3 4 5 6     7 8 9 10 11
+        let trees = build("fun f(a) { print (a); }");
+        assert!(trees.errors.is_empty());
+        assert_eq!(trees.trees.len(), 4);
+
+        let TokenTree::Group(body) = &trees.trees[3] else {
+            panic!("expected a group, got {:?}", trees.trees[3]);
+        };
+        assert_eq!(body.delimiter, Delimiter::Brace);
+        assert_eq!((body.open, body.close), (5, Some(11)));
+        assert_eq!(
+            body.trees[1],
+            TokenTree::Group(Group {
+                delimiter: Delimiter::Paren,
+                open: 7,
+                close: Some(9),
+                trees: vec![TokenTree::Token(8)],
+            })
+        );
+
+        assert_eq!(trees.matching_delimiter(2), Some(4));
+        assert_eq!(trees.matching_delimiter(11), Some(5));
+        assert_eq!(trees.matching_delimiter(1), None);
+    }
+
+    #[test]
+    fn reports_mismatched_delimiters_with_both_spans() {
+        let trees = build("{ a )");
+        assert_eq!(
+            trees.errors,
+            [
+                DelimiterError::Mismatched {
+                    open_delimiter: Delimiter::Brace,
+                    open: Span {
+                        file_id: Default::default(),
+                        start: 0,
+                        end: 1
+                    },
+                    close_delimiter: Delimiter::Paren,
+                    close: Span {
+                        file_id: Default::default(),
+                        start: 4,
+                        end: 5
+                    },
+                },
+                DelimiterError::Unclosed {
+                    delimiter: Delimiter::Brace,
+                    open: Span {
+                        file_id: Default::default(),
+                        start: 0,
+                        end: 1
+                    },
+                },
+            ]
+        );
+        assert_eq!(
+            trees.errors[0].to_string(),
+            "mismatched delimiters: `{` opened here, `)` found here"
+        );
+        // The ")" stays inside of the group as a plain token.
+        let TokenTree::Group(group) = &trees.trees[0] else {
+            panic!("expected a group");
+        };
+        assert_eq!(group.trees, [TokenTree::Token(1), TokenTree::Token(2)]);
+    }
+
+    #[test]
+    fn closing_delimiter_cuts_short_inner_groups() {
+        // { f ( ( a }
+        let trees = build("{ f((a }");
+        assert!(matches!(
+            trees.errors[..],
+            [
+                DelimiterError::Mismatched {
+                    open_delimiter: Delimiter::Paren,
+                    open: Span { start: 4, .. },
+                    close_delimiter: Delimiter::Brace,
+                    ..
+                },
+                DelimiterError::Unclosed {
+                    delimiter: Delimiter::Paren,
+                    open: Span { start: 3, .. },
+                },
+            ]
+        ));
+        assert_eq!(trees.matching_delimiter(0), Some(5));
+        assert_eq!(trees.matching_delimiter(2), None);
+    }
+
+    #[test]
+    fn reports_unexpected_and_unclosed_delimiters() {
+        let trees = build(") (");
+        assert!(matches!(
+            trees.errors[..],
+            [
+                DelimiterError::Unexpected {
+                    delimiter: Delimiter::Paren,
+                    ..
+                },
+                DelimiterError::Unclosed {
+                    delimiter: Delimiter::Paren,
+                    ..
+                },
+            ]
+        ));
+        assert_eq!(trees.trees.len(), 2);
+    }
+}
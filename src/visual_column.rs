@@ -0,0 +1,97 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::lox_language_scanner::{Token, UnexpectedSymbolError};
+
+// Most editors default to 4 columns per tab.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+// Column at which the byte `index` of `line` is displayed in a terminal or editor.
+// A tab advances to the next multiple of `tab_width`, East Asian wide characters take two cells
+// and zero-width characters take none. An index in the middle of a character points at that character.
+// A `tab_width` of 0 is treated as 1, so a tab takes one cell.
+pub fn visual_column(line: &str, index: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    let mut column = 0;
+    for (i, c) in line.char_indices() {
+        if i + c.len_utf8() > index {
+            break;
+        }
+        column += match c {
+            '\t' => tab_width - column % tab_width,
+            c => c.width().unwrap_or(0),
+        };
+    }
+    column
+}
+
+// Replaces tabs with spaces, so the line is displayed the same way `visual_column` counts.
+pub fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            out.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            out.push(c);
+            column += c.width().unwrap_or(0);
+        }
+    }
+    out
+}
+
+impl Token<'_> {
+    // Visual columns of the beginning and the end of the token. `src` must be the input the token was scanned from.
+    pub fn visual_columns(&self, src: &str, tab_width: usize) -> (usize, usize) {
+        let line = &src[self.line_start_index..];
+        (
+            visual_column(line, self.start_index_within_line, tab_width),
+            visual_column(line, self.end_index_within_line, tab_width),
+        )
+    }
+}
+
+impl UnexpectedSymbolError {
+    pub fn visual_column(&self, src: &str, tab_width: usize) -> usize {
+        visual_column(
+            &src[self.line_start_index_within_input..],
+            self.error_index_within_line,
+            tab_width,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox_language_scanner::Scanner;
+
+    #[test]
+    fn tabs_advance_to_next_tab_stop() {
+        assert_eq!(visual_column("\tx", 1, 4), 4);
+        assert_eq!(visual_column("ab\tx", 3, 4), 4);
+        assert_eq!(visual_column("ab\tx", 3, 8), 8);
+        assert_eq!(visual_column("abcd\tx", 5, 4), 8);
+        assert_eq!(expand_tabs("ab\tx", 4), "ab  x");
+    }
+
+    #[test]
+    fn zero_tab_width_is_one_cell() {
+        assert_eq!(visual_column("a\t\tx", 3, 0), 3);
+        assert_eq!(expand_tabs("a\t\tx", 0), "a  x");
+    }
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        let src = "\"日本\" + \"é\" @";
+        let err = Scanner::new(src).find_map(Result::err).unwrap();
+        let err = err.downcast_ref::<UnexpectedSymbolError>().unwrap();
+        assert_eq!(err.error_index_within_line, src.find('@').unwrap());
+        assert_eq!(err.visual_column(src, 4), 13);
+
+        let tokens: Vec<_> = Scanner::new("\t\"日本\"").map(|r| r.unwrap()).collect();
+        assert_eq!(tokens[0].visual_columns("\t\"日本\"", 4), (5, 9));
+    }
+}
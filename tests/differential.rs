@@ -0,0 +1,213 @@
+#![cfg(feature = "std")]
+
+// Checks the re2c generated scanner against the hand-written reference scanner on random and mutated inputs.
+// Both must agree on every token, position and error.
+
+use anyhow::Result;
+use lox_scanner::lox_language_scanner::{
+    ConfusableCharacterError, Scanner, Token, UnexpectedSymbolError, UnterminatedStringError,
+};
+
+// Test oracle, kept out of the library API
+#[path = "support/reference_scanner.rs"]
+mod reference_scanner;
+use reference_scanner::ReferenceScanner;
+
+const ITERATIONS: usize = 1000;
+
+// Pieces of Lox code and of near-Lox code that are likely to expose differences between the scanners.
+const FRAGMENTS: &[&str] = &[
+    "and",
+    "class",
+    "else",
+    "false",
+    "for",
+    "fun",
+    "if",
+    "nil",
+    "or",
+    "print",
+    "return",
+    "super",
+    "this",
+    "true",
+    "var",
+    "while",
+    "classy",
+    "orchid",
+    "fork",
+    "_",
+    "x",
+    "x1",
+    "(",
+    ")",
+    "{",
+    "}",
+    ",",
+    ".",
+    "-",
+    "+",
+    ";",
+    "/",
+    "*",
+    "!",
+    "!=",
+    "=",
+    "==",
+    ">",
+    ">=",
+    "<",
+    "<=",
+    "%",
+    "+=",
+    "/=",
+    "**",
+    "--",
+    "?",
+    ":",
+    "0",
+    "123",
+    "1.5",
+    "1.",
+    ".5",
+    "\"",
+    "\"str\"",
+    "\"",
+    "\"a${",
+    "${",
+    "$",
+    "r",
+    "r\"",
+    "r#\"",
+    "\"#",
+    "\u{201C}smart\u{201D}",
+    "\u{201E}",
+    "\u{201D}",
+    "//",
+    "// comment",
+    " ",
+    "  ",
+    "\t",
+    "\n",
+    "\r",
+    "\r\n",
+    "\n\r",
+    "@",
+    "#",
+    "\u{e9}",
+    "\u{65E5}\u{672C}",
+    "\u{1F60A}",
+    "\u{2013}",
+    "\u{FF1B}",
+    "\u{A0}",
+    "\u{2260}",
+    "\u{feff}",
+];
+
+const SEED_PROGRAMS: &[&str] = &[
+    "var x = 42; // the answer\nprint x >= 3.14 and !false;\n",
+    "fun fib(n) {\r\n  if (n <= 1) return n;\r\n  return fib(n - 2) + fib(n - 1);\r\n}\r\n",
+    "class Point {\n\r  init(x, y) { this.x = x; this.y = y; }\n\r}\n\rprint \"done\";",
+    "while (i != 10) { i = i * 2 / 1; } print \u{201C}quoted\u{201D};",
+    "print \"Hello ${ name }, ${ {a}.b + \"${c}\" }!\" + r#\"raw \"text\"\"#;\n",
+];
+
+// xorshift64*. Deterministic, so a failure can be reproduced.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn fragment(&mut self) -> &'static str {
+        FRAGMENTS[self.below(FRAGMENTS.len())]
+    }
+}
+
+fn random_input(rng: &mut Rng) -> String {
+    let len = rng.below(40);
+    (0..len).map(|_| rng.fragment()).collect()
+}
+
+fn char_boundary(rng: &mut Rng, s: &str) -> usize {
+    let boundaries: Vec<usize> = s.char_indices().map(|(i, _)| i).chain([s.len()]).collect();
+    boundaries[rng.below(boundaries.len())]
+}
+
+fn mutated_input(rng: &mut Rng) -> String {
+    let mut s = SEED_PROGRAMS[rng.below(SEED_PROGRAMS.len())].to_string();
+    for _ in 0..1 + rng.below(5) {
+        let a = char_boundary(rng, &s);
+        let b = char_boundary(rng, &s);
+        let (a, b) = (a.min(b), a.max(b));
+        match rng.below(3) {
+            0 => s.insert_str(a, rng.fragment()),
+            1 => s.replace_range(a..b, ""),
+            _ => {
+                let copy = s[a..b].to_string();
+                s.insert_str(b, &copy);
+            }
+        }
+    }
+    s
+}
+
+// Errors are compared by their concrete type and fields.
+fn describe(item: &Result<Token>) -> String {
+    match item {
+        Ok(token) => format!("{token:?}"),
+        Err(err) => {
+            if let Some(err) = err.downcast_ref::<UnexpectedSymbolError>() {
+                format!("{err:?}")
+            } else if let Some(err) = err.downcast_ref::<ConfusableCharacterError>() {
+                format!("{err:?}")
+            } else if let Some(err) = err.downcast_ref::<UnterminatedStringError>() {
+                format!("{err:?}")
+            } else {
+                format!("unknown error: {err}")
+            }
+        }
+    }
+}
+
+fn assert_scanners_agree(src: &str) {
+    let actual: Vec<_> = Scanner::new(src).map(|item| describe(&item)).collect();
+    let expected: Vec<_> = ReferenceScanner::new(src)
+        .map(|item| describe(&item))
+        .collect();
+    assert_eq!(actual, expected, "Scanners disagree on input {src:?}");
+}
+
+#[test]
+fn scanners_agree_on_seed_programs() {
+    for src in SEED_PROGRAMS {
+        assert_scanners_agree(src);
+    }
+    for fragment in FRAGMENTS {
+        assert_scanners_agree(fragment);
+    }
+}
+
+#[test]
+fn scanners_agree_on_random_inputs() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..ITERATIONS {
+        assert_scanners_agree(&random_input(&mut rng));
+    }
+}
+
+#[test]
+fn scanners_agree_on_mutated_programs() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for _ in 0..ITERATIONS {
+        assert_scanners_agree(&mutated_input(&mut rng));
+    }
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "std")]
+
+// Runs the invariant checks of the fuzz targets on the seed corpus, so they are checked on every `cargo test`,
+// not only when fuzzing.
+
+use std::fs;
+use std::path::Path;
+
+use lox_scanner::lox_language_scanner::{NewlinePolicy, Scanner};
+
+#[path = "../fuzz/src/invariants.rs"]
+mod invariants;
+
+#[test]
+fn seed_corpus_satisfies_invariants() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/scan");
+    let mut checked = 0;
+    for entry in fs::read_dir(corpus).unwrap() {
+        let src = fs::read_to_string(entry.unwrap().path()).unwrap();
+        for policy in [
+            NewlinePolicy::Any,
+            NewlinePolicy::Unix,
+            NewlinePolicy::Windows,
+            NewlinePolicy::EditorCompatible,
+        ] {
+            for options in [false, true] {
+                let items: Vec<_> = Scanner::new(&src)
+                    .newline_policy(policy)
+                    .report_mixed_line_endings(options)
+                    .skip_shebang(options)
+                    .trivia(options)
+                    .collect();
+                invariants::check_invariants(&src, &items);
+            }
+        }
+        checked += 1;
+    }
+    assert!(checked > 0, "the seed corpus is empty");
+}
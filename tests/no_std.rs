@@ -0,0 +1,34 @@
+use std::path::Path;
+use std::process::Command;
+
+// Builds the library without `std` for a bare-metal target to make sure that the scanner only depends on `core`.
+// The target has to be installed first: `rustup target add thumbv7em-none-eabihf`. CI runs it with `--include-ignored`.
+#[test]
+#[ignore = "requires the thumbv7em-none-eabihf target"]
+fn no_std_build_compiles_for_bare_metal_target() {
+    let status = Command::new(env!("CARGO"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["build", "--lib", "--no-default-features"])
+        .args(["--target", "thumbv7em-none-eabihf"])
+        .arg("--target-dir")
+        .arg(Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_std"))
+        .status()
+        .expect("failed to run cargo");
+
+    assert!(status.success(), "no_std build failed");
+}
+
+#[cfg(not(feature = "std"))]
+#[test]
+fn no_std_errors_are_plain_values() {
+    use lox_scanner::lox_language_scanner::*;
+
+    let err = Scanner::new("@").next().unwrap().unwrap_err();
+    let copy = err;
+    assert_eq!(err, copy);
+
+    let ScanError::UnexpectedSymbol(e) = err else {
+        panic!("Expected ScanError::UnexpectedSymbol, got {err:?}");
+    };
+    assert_eq!(e.error_index_within_input, 0);
+}
@@ -0,0 +1,178 @@
+#![cfg(feature = "std")]
+
+// Property-based tests: random valid token sequences are rendered to source with random whitespace and comments,
+// and the scanner has to return exactly the same sequence.
+
+use lox_scanner::lox_language_scanner::{Scanner, TokenKind, TokenType};
+use lox_scanner::minify::Minifier;
+use lox_scanner::token_printer::tokens_to_source;
+use proptest::prelude::*;
+
+#[derive(Debug, Clone)]
+enum GenToken {
+    Fixed(TokenKind),
+    Identifier(String),
+    String(String),
+    Number(String),
+}
+
+impl GenToken {
+    fn text(&self) -> String {
+        match self {
+            GenToken::Fixed(kind) => kind.fixed_lexeme().unwrap().to_string(),
+            GenToken::Identifier(name) => name.clone(),
+            GenToken::String(content) => format!("\"{content}\""),
+            GenToken::Number(digits) => digits.clone(),
+        }
+    }
+
+    fn token_type(&self) -> TokenType<'_> {
+        match self {
+            GenToken::Fixed(kind) => kind.fixed_token_type().unwrap(),
+            GenToken::Identifier(name) => TokenType::IDENTIFIER(name),
+            GenToken::String(content) => TokenType::STRING(content),
+            GenToken::Number(digits) => TokenType::NUMBER(digits.parse().unwrap()),
+        }
+    }
+}
+
+fn fixed_lexemes() -> impl Iterator<Item = &'static str> {
+    TokenKind::ALL.iter().filter_map(|kind| kind.fixed_lexeme())
+}
+
+fn is_keyword(name: &str) -> bool {
+    fixed_lexemes().any(|lexeme| lexeme == name)
+}
+
+fn keyword() -> impl Strategy<Value = &'static str> {
+    let keywords: Vec<_> = fixed_lexemes()
+        .filter(|lexeme| lexeme.starts_with(|c: char| c.is_ascii_alphabetic()))
+        .collect();
+    proptest::sample::select(keywords)
+}
+
+// Random names, and names that start or end like keywords, such as `classy` or `orchid`.
+fn identifier() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[A-Za-z_][A-Za-z0-9_]{0,8}",
+        (keyword(), "[A-Za-z0-9_]{1,3}")
+            .prop_map(|(keyword, suffix)| keyword.to_string() + &suffix),
+        ("[A-Za-z_][A-Za-z0-9_]{0,2}", keyword()).prop_map(|(prefix, keyword)| prefix + keyword),
+    ]
+    .prop_filter("keywords aren't identifiers", |name| !is_keyword(name))
+}
+
+fn token() -> impl Strategy<Value = GenToken> {
+    let fixed: Vec<_> = TokenKind::ALL
+        .iter()
+        .copied()
+        .filter(|kind| kind.fixed_lexeme().is_some())
+        .collect();
+    prop_oneof![
+        4 => proptest::sample::select(fixed).prop_map(GenToken::Fixed),
+        2 => identifier().prop_map(GenToken::Identifier),
+        1 => "[^\"\r\n]{0,10}"
+            .prop_filter("\"${\" starts an interpolation", |content| !content.contains("${"))
+            .prop_map(GenToken::String),
+        1 => "[0-9]{1,5}(\\.[0-9]{1,3})?".prop_map(GenToken::Number),
+    ]
+}
+
+// Whitespace and comments between two tokens. Empty most of the time, to exercise maximal munch.
+fn separator() -> impl Strategy<Value = String> {
+    prop_oneof![
+        4 => Just(String::new()),
+        2 => "[ \t]{1,3}",
+        1 => prop_oneof![Just("\n"), Just("\r\n"), Just("\r"), Just("\n\r")].prop_map(String::from),
+        1 => "[^\r\n]{0,10}".prop_map(|text| format!(" //{text}\n")),
+    ]
+}
+
+// Whether two tokens written next to each other would be scanned differently.
+fn needs_space(prev: &GenToken, next: &GenToken) -> bool {
+    let (prev, next) = (prev.text(), next.text());
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let joined = prev.clone() + &next;
+    prev.ends_with(is_word) && next.starts_with(is_word)
+        // `1` followed by `.5` would be `1.5`
+        || prev.ends_with(|c: char| c.is_ascii_digit()) && next.starts_with('.')
+        // `/` followed by `/` starts a comment
+        || prev.ends_with('/') && next.starts_with('/')
+        // `r` followed by a string is a raw string
+        || prev == "r" && next.starts_with('"')
+        // `!` followed by `=` is `!=`
+        || fixed_lexemes().any(|lexeme| lexeme.len() > prev.len() && joined.starts_with(lexeme))
+}
+
+fn render(tokens: &[GenToken], separators: &[String]) -> String {
+    let mut src = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 {
+            let separator = &separators[i % separators.len()];
+            if separator.is_empty() && needs_space(&tokens[i - 1], token) {
+                src.push(' ');
+            } else {
+                src.push_str(separator);
+            }
+        }
+        src.push_str(&token.text());
+    }
+    src
+}
+
+fn scan(src: &str) -> Vec<TokenType<'_>> {
+    Scanner::new(src)
+        .map(|item| item.unwrap().token_type)
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn scanner_returns_rendered_tokens(
+        tokens in prop::collection::vec(token(), 0..40),
+        separators in prop::collection::vec(separator(), 1..8),
+    ) {
+        let src = render(&tokens, &separators);
+        let expected: Vec<_> = tokens.iter().map(GenToken::token_type).collect();
+        prop_assert_eq!(scan(&src), expected, "source: {:?}", src);
+    }
+
+    #[test]
+    fn rerendering_is_stable(
+        tokens in prop::collection::vec(token(), 0..40),
+        separators in prop::collection::vec(separator(), 1..8),
+    ) {
+        let src = render(&tokens, &separators);
+        let scanned: Vec<_> = Scanner::new(&src).map(|item| item.unwrap()).collect();
+        let rendered = tokens_to_source(&scanned);
+
+        let rescanned: Vec<_> = Scanner::new(&rendered).map(|item| item.unwrap()).collect();
+        prop_assert_eq!(
+            rescanned.iter().map(|t| &t.token_type).collect::<Vec<_>>(),
+            scanned.iter().map(|t| &t.token_type).collect::<Vec<_>>()
+        );
+        prop_assert_eq!(tokens_to_source(&rescanned), rendered);
+    }
+
+    #[test]
+    fn minified_source_scans_to_same_tokens(
+        tokens in prop::collection::vec(token(), 0..40),
+        separators in prop::collection::vec(separator(), 1..8),
+    ) {
+        let src = render(&tokens, &separators);
+        let minified = Minifier::new().minify(&src).unwrap();
+        prop_assert_eq!(scan(&minified), scan(&src), "minified: {:?}", minified);
+        prop_assert!(minified.len() <= src.len());
+    }
+
+    #[test]
+    fn renaming_keeps_token_kinds(
+        tokens in prop::collection::vec(token(), 0..40),
+        separators in prop::collection::vec(separator(), 1..8),
+    ) {
+        let src = render(&tokens, &separators);
+        let minified = Minifier::new().rename_identifiers(true).minify(&src).unwrap();
+        let kinds = |src| scan(src).iter().map(TokenType::kind).collect::<Vec<_>>();
+        prop_assert_eq!(kinds(&minified), kinds(&src), "minified: {:?}", minified);
+    }
+}
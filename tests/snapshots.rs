@@ -0,0 +1,113 @@
+#![cfg(feature = "std")]
+
+// Golden snapshot tests. Every `tests/fixtures/*.lox` file is scanned and the token stream is compared
+// with the `.tokens` file next to it. To add a test case, drop in a `.lox` file and run
+// `BLESS=1 cargo test --test snapshots`, which writes the snapshots instead of comparing them.
+// Review the changes of the `.tokens` files before committing them.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lox_scanner::diagnostics::error_location;
+use lox_scanner::lox_language_scanner::{Scanner, TokenType};
+
+// One line per token or error. Positions are 0-based, like everywhere in the scanner:
+//
+// 0:4..5 IDENTIFIER "x"
+// 1:6 error: Unexpected symbol at 1:6
+fn dump(src: &str) -> String {
+    let mut out = String::new();
+    for item in Scanner::new(src) {
+        match item {
+            Ok(token) => {
+                write!(
+                    out,
+                    "{}:{}..{} {}",
+                    token.line_number,
+                    token.start_index_within_line,
+                    token.end_index_within_line,
+                    token.token_type.kind()
+                )
+                .unwrap();
+                match token.token_type {
+                    TokenType::IDENTIFIER(text)
+                    | TokenType::STRING(text)
+                    | TokenType::STRING_START(text)
+                    | TokenType::STRING_PART(text)
+                    | TokenType::STRING_END(text)
+                    | TokenType::RAW_STRING(text) => write!(out, " {text:?}").unwrap(),
+                    TokenType::NUMBER(value) => write!(out, " {value}").unwrap(),
+                    _ => {}
+                }
+                out.push('\n');
+            }
+            Err(err) => {
+                let location = error_location(&err).expect("scanner errors have a location");
+                writeln!(
+                    out,
+                    "{}:{} error: {err}",
+                    location.line_number,
+                    location.index_within_input - location.line_start_index
+                )
+                .unwrap();
+            }
+        }
+    }
+    out
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+#[test]
+fn token_streams_match_snapshots() {
+    let bless = env::var_os("BLESS").is_some_and(|value| value != "0");
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no fixtures found");
+
+    let mut failures = Vec::new();
+    for fixture in fixtures {
+        let src = fs::read_to_string(&fixture).unwrap();
+        let actual = dump(&src);
+        let snapshot = fixture.with_extension("tokens");
+
+        if bless {
+            fs::write(&snapshot, &actual).unwrap();
+            continue;
+        }
+
+        let Ok(expected) = fs::read_to_string(&snapshot) else {
+            failures.push(format!("{}: snapshot is missing", snapshot.display()));
+            continue;
+        };
+        if let Some((i, (expected, actual))) = expected
+            .lines()
+            .chain(["<end>"])
+            .zip(actual.lines().chain(["<end>"]))
+            .enumerate()
+            .find(|(_, (expected, actual))| expected != actual)
+        {
+            failures.push(format!(
+                "{}:{}: expected `{expected}`, got `{actual}`",
+                snapshot.display(),
+                i + 1
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "Token streams differ from the snapshots. Run with BLESS=1 to update them.\n{}",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,417 @@
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use lox_scanner::lox_language_scanner::{
+    Confusable, ConfusableCharacterError, FileId, MAX_INTERPOLATION_DEPTH, Token, TokenKind,
+    TokenType, UnexpectedSymbolError, UnterminatedStringError,
+};
+
+const QUOTES: [char; 4] = ['"', '\u{201C}', '\u{201D}', '\u{201E}'];
+
+// Rules of the re2c scanner, in the order they are listed there. The longest match wins,
+// and of two matches of the same length the rule listed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    LineBreak,
+    Whitespace,
+    Comment,
+    Number,
+    String,
+    InterpolatedStringStart,
+    SmartQuotedString,
+    RawStringStart,
+    Fixed(TokenKind),
+    Identifier,
+    AnyCharacter,
+}
+
+// Straightforward hand-written scanner that returns exactly what `Scanner::new` returns, with the default options.
+// It isn't meant to be fast. It exists to check the re2c generated scanner against it in differential tests.
+pub struct ReferenceScanner<'a> {
+    src: &'a str,
+    pos: usize,
+    line_number: usize,
+    line_start: usize,
+    pending: VecDeque<Result<Token<'a>>>,
+    // Open interpolations, innermost last: (opening quotation mark of the string, braces opened inside).
+    interpolations: Vec<(usize, usize)>,
+    // In the rest of an interpolated string after an interpolation, which starts at `string_start`.
+    in_string: bool,
+    string_start: usize,
+}
+
+impl<'a> ReferenceScanner<'a> {
+    pub fn new(src: &'a str) -> Self {
+        let bom_len = if src.starts_with('\u{feff}') {
+            '\u{feff}'.len_utf8()
+        } else {
+            0
+        };
+        Self {
+            src,
+            pos: bom_len,
+            line_number: 0,
+            line_start: bom_len,
+            pending: VecDeque::new(),
+            interpolations: Vec::new(),
+            in_string: false,
+            string_start: 0,
+        }
+    }
+
+    // Finds the rule that matches at the current position, and the length of the match.
+    fn longest_match(&self) -> (Rule, usize) {
+        let rest = &self.src[self.pos..];
+        let fixed = TokenKind::ALL.iter().filter_map(|&kind| {
+            let lexeme = kind.fixed_lexeme()?;
+            rest.starts_with(lexeme)
+                .then_some((Rule::Fixed(kind), lexeme.len()))
+        });
+
+        [
+            (Rule::LineBreak, line_break_len(rest)),
+            (
+                Rule::Whitespace,
+                prefix_len(rest, |c| c == ' ' || c == '\t'),
+            ),
+            (Rule::Comment, comment_len(rest)),
+            (Rule::Number, number_len(rest)),
+            (Rule::String, string_len(rest)),
+            (
+                Rule::InterpolatedStringStart,
+                interpolated_string_start_len(rest),
+            ),
+            (Rule::SmartQuotedString, smart_quoted_string_len(rest)),
+            (Rule::RawStringStart, raw_string_start_len(rest)),
+        ]
+        .into_iter()
+        .chain(fixed)
+        .chain([
+            (Rule::Identifier, identifier_len(rest)),
+            (
+                Rule::AnyCharacter,
+                rest.chars().next().map_or(0, char::len_utf8),
+            ),
+        ])
+        .filter(|&(_, len)| len > 0)
+        // Of several equally long matches `min_by_key` returns the first one.
+        .min_by_key(|&(_, len)| Reverse(len))
+        .expect("any character matches")
+    }
+
+    fn token(&self, token_type: TokenType<'a>, beg: usize, end: usize) -> Result<Token<'a>> {
+        Ok(Token {
+            token_type,
+            file_id: FileId::default(),
+            line_number: self.line_number,
+            line_start_index: self.line_start,
+            start_index_within_input: beg,
+            end_index_within_input: end,
+            start_index_within_line: beg - self.line_start,
+            end_index_within_line: end - self.line_start,
+        })
+    }
+
+    // Returns `None` if the character at `beg` isn't a known confusable.
+    fn confusable_error(&self, beg: usize) -> Option<Result<Token<'a>>> {
+        let symbol = self.src[beg..].chars().next()?;
+        let confusable = Confusable::find(symbol)?;
+        Some(Err(ConfusableCharacterError {
+            file_id: FileId::default(),
+            line_number: self.line_number,
+            line_start_index_within_input: self.line_start,
+            error_index_within_line: beg - self.line_start,
+            error_index_within_input: beg,
+            error_end_index_within_input: beg + symbol.len_utf8(),
+            symbol,
+            name: confusable.name,
+            suggestion: confusable.suggestion,
+        }
+        .into()))
+    }
+
+    // Drops all open interpolations, like the re2c scanner does.
+    fn unterminated_string_error(&mut self, beg: usize) -> Result<Token<'a>> {
+        let string_start = self
+            .interpolations
+            .first()
+            .map_or(self.string_start, |&(string_start, _)| string_start);
+        self.interpolations.clear();
+        self.in_string = false;
+        self.string_error(beg, string_start)
+    }
+
+    fn string_error(&self, beg: usize, string_start: usize) -> Result<Token<'a>> {
+        Err(UnterminatedStringError {
+            file_id: FileId::default(),
+            line_number: self.line_number,
+            line_start_index_within_input: self.line_start,
+            error_index_within_line: beg - self.line_start,
+            error_index_within_input: beg,
+            string_start_index_within_input: string_start,
+        }
+        .into())
+    }
+
+    // Text of an interpolated string after an interpolation, up to the next interpolation or the closing quotation mark.
+    fn string_continuation(&mut self) -> Result<Token<'a>> {
+        let beg = self.pos;
+        let rest = &self.src[beg..];
+        let content_len = string_content_len(rest);
+        let (content, after) = rest.split_at(content_len);
+        self.in_string = false;
+        if after.starts_with("${") {
+            self.pos += content_len + 2;
+            // Nested too deep: the rest of the string is text, like in the re2c scanner
+            if self.interpolations.len() == MAX_INTERPOLATION_DEPTH {
+                self.in_string = true;
+                self.pending
+                    .push_back(self.unexpected_symbol_error(beg + content_len));
+            } else {
+                self.interpolations.push((self.string_start, 0));
+            }
+            self.token(TokenType::STRING_PART(content), beg, beg + content_len)
+        } else if after.starts_with('"') {
+            self.pos += content_len + 1;
+            self.token(TokenType::STRING_END(content), beg, beg + content_len)
+        } else {
+            self.in_string = true;
+            self.unterminated_string_error(beg)
+        }
+    }
+
+    fn unexpected_symbol_error(&self, beg: usize) -> Result<Token<'a>> {
+        Err(UnexpectedSymbolError {
+            file_id: FileId::default(),
+            line_number: self.line_number,
+            line_start_index_within_input: self.line_start,
+            error_index_within_line: beg - self.line_start,
+            error_index_within_input: beg,
+        }
+        .into())
+    }
+}
+
+impl<'a> Iterator for ReferenceScanner<'a> {
+    type Item = Result<Token<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(item);
+            }
+            if self.in_string {
+                return Some(self.string_continuation());
+            }
+            if self.pos >= self.src.len() {
+                if !self.interpolations.is_empty() {
+                    return Some(self.unterminated_string_error(self.pos));
+                }
+                return None;
+            }
+
+            let (rule, len) = self.longest_match();
+            let (beg, end) = (self.pos, self.pos + len);
+            let text = &self.src[beg..end];
+            self.pos = end;
+
+            match rule {
+                Rule::LineBreak => {
+                    if !self.interpolations.is_empty() {
+                        let error = self.unterminated_string_error(beg);
+                        self.pending.push_back(error);
+                    }
+                    self.line_number += 1;
+                    self.line_start = end;
+                }
+                Rule::Whitespace | Rule::Comment => {}
+                Rule::Number => {
+                    return Some(self.token(TokenType::NUMBER(text.parse().unwrap()), beg, end));
+                }
+                Rule::String => {
+                    let content = &text[1..len - 1];
+                    return Some(self.token(TokenType::STRING(content), beg + 1, end - 1));
+                }
+                Rule::InterpolatedStringStart => {
+                    let (content_beg, content_end) = (beg + 1, end - 2);
+                    let content = &self.src[content_beg..content_end];
+                    let token =
+                        self.token(TokenType::STRING_START(content), content_beg, content_end);
+                    if self.interpolations.len() == MAX_INTERPOLATION_DEPTH {
+                        self.string_start = beg;
+                        self.in_string = true;
+                        self.pending
+                            .push_back(self.unexpected_symbol_error(content_end));
+                    } else {
+                        self.interpolations.push((beg, 0));
+                    }
+                    return Some(token);
+                }
+                Rule::SmartQuotedString => {
+                    let open_len = text.chars().next().unwrap().len_utf8();
+                    let close_len = text.chars().next_back().unwrap().len_utf8();
+                    let (content_beg, content_end) = (beg + open_len, end - close_len);
+                    let content = &self.src[content_beg..content_end];
+
+                    let open_error = self.confusable_error(beg);
+                    let string = self.token(TokenType::STRING(content), content_beg, content_end);
+                    let close_error = self.confusable_error(content_end);
+                    self.pending.extend(open_error);
+                    self.pending.push_back(string);
+                    self.pending.extend(close_error);
+                }
+                Rule::RawStringStart => {
+                    let hashes = len - 2;
+                    let line = &self.src[end..];
+                    let line = &line[..prefix_len(line, |c| c != '\r' && c != '\n')];
+                    let closing = "\"".to_string() + &"#".repeat(hashes);
+                    let Some(content_len) = line.find(&closing) else {
+                        self.pos = end + line.len();
+                        return Some(self.string_error(self.pos, beg));
+                    };
+                    self.pos = end + content_len + closing.len();
+                    let content = &line[..content_len];
+                    return Some(self.token(
+                        TokenType::RAW_STRING(content),
+                        end,
+                        end + content_len,
+                    ));
+                }
+                Rule::Fixed(TokenKind::LEFT_BRACE) => {
+                    if let Some((_, open_braces)) = self.interpolations.last_mut() {
+                        *open_braces += 1;
+                    }
+                    return Some(self.token(TokenType::LEFT_BRACE, beg, end));
+                }
+                Rule::Fixed(TokenKind::RIGHT_BRACE) => match self.interpolations.last_mut() {
+                    Some((string_start, 0)) => {
+                        self.string_start = *string_start;
+                        self.interpolations.pop();
+                        self.in_string = true;
+                    }
+                    Some((_, open_braces)) => {
+                        *open_braces -= 1;
+                        return Some(self.token(TokenType::RIGHT_BRACE, beg, end));
+                    }
+                    None => return Some(self.token(TokenType::RIGHT_BRACE, beg, end)),
+                },
+                Rule::Fixed(kind) => {
+                    return Some(self.token(kind.fixed_token_type().unwrap(), beg, end));
+                }
+                Rule::Identifier => {
+                    return Some(self.token(TokenType::IDENTIFIER(text), beg, end));
+                }
+                Rule::AnyCharacter => {
+                    return Some(
+                        self.confusable_error(beg)
+                            .unwrap_or_else(|| self.unexpected_symbol_error(beg)),
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn prefix_len(s: &str, pred: impl Fn(char) -> bool) -> usize {
+    s.find(|c| !pred(c)).unwrap_or(s.len())
+}
+
+fn line_break_len(s: &str) -> usize {
+    if s.starts_with("\r\n") || s.starts_with("\n\r") {
+        2
+    } else if s.starts_with(['\r', '\n']) {
+        1
+    } else {
+        0
+    }
+}
+
+fn comment_len(s: &str) -> usize {
+    if s.starts_with("//") {
+        prefix_len(s, |c| c != '\r' && c != '\n')
+    } else {
+        0
+    }
+}
+
+fn number_len(s: &str) -> usize {
+    let integer = prefix_len(s, |c| c.is_ascii_digit());
+    if integer == 0 {
+        return 0;
+    }
+    let fraction = match s[integer..].strip_prefix('.') {
+        Some(rest) => prefix_len(rest, |c| c.is_ascii_digit()),
+        None => 0,
+    };
+    if fraction > 0 {
+        integer + 1 + fraction
+    } else {
+        integer
+    }
+}
+
+// Text of a string: up to the end of the line, a quotation mark or "${".
+fn string_content_len(s: &str) -> usize {
+    let end = prefix_len(s, |c| c != '\r' && c != '\n' && c != '"');
+    s[..end].find("${").unwrap_or(end)
+}
+
+fn string_len(s: &str) -> usize {
+    let Some(rest) = s.strip_prefix('"') else {
+        return 0;
+    };
+    let content = string_content_len(rest);
+    if rest[content..].starts_with('"') {
+        content + 2
+    } else {
+        0
+    }
+}
+
+fn interpolated_string_start_len(s: &str) -> usize {
+    let Some(rest) = s.strip_prefix('"') else {
+        return 0;
+    };
+    let content = string_content_len(rest);
+    if rest[content..].starts_with("${") {
+        content + 3
+    } else {
+        0
+    }
+}
+
+// Only the r"" prefix, like the re2c rule. The scanner looks for the end of the raw string itself.
+fn raw_string_start_len(s: &str) -> usize {
+    let Some(rest) = s.strip_prefix('r') else {
+        return 0;
+    };
+    let hashes = prefix_len(rest, |c| c == '#');
+    if rest[hashes..].starts_with('"') {
+        hashes + 2
+    } else {
+        0
+    }
+}
+
+// Opening and closing quotation marks can be "\"" or typographic ones, in any combination.
+fn smart_quoted_string_len(s: &str) -> usize {
+    let Some(open) = s.chars().next().filter(|c| QUOTES.contains(c)) else {
+        return 0;
+    };
+    let rest = &s[open.len_utf8()..];
+    let content = prefix_len(rest, |c| c != '\r' && c != '\n' && !QUOTES.contains(&c));
+    let content = rest[..content].find("${").unwrap_or(content);
+    match rest[content..].chars().next() {
+        Some(close) if QUOTES.contains(&close) => open.len_utf8() + content + close.len_utf8(),
+        _ => 0,
+    }
+}
+
+fn identifier_len(s: &str) -> usize {
+    if !s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return 0;
+    }
+    prefix_len(s, |c| c.is_ascii_alphanumeric() || c == '_')
+}